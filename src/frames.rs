@@ -0,0 +1,215 @@
+//! Frame patterns for banks of LEDs
+//!
+//! A [`FramePattern`] holds one [`Frame`] per step, a word with one bit
+//! for each pin of a bank, like an LED bargraph. Frames are played by a
+//! [`FrameBlinq`], which is a [`Blinq`] driving a [`Bank`] of on/off
+//! outputs, like GPIOs wrapped in a `Gpio` or `Gpio1`. Bit `0` of each
+//! frame drives the first pin.
+//!
+//! ## Example
+//!
+//! ```rust
+//! # let leds = &[(); 4].map(|_| core::cell::Cell::new(false));
+//! #
+//! use blinq::frames::{Bank, FrameBlinq, FramePattern};
+//!
+//! // A bank of four LEDs, with room for 2 patterns of up to 8 frames each
+//! let pins = [0, 1, 2, 3].map(|i| move |on: bool| leds[i].set(on));
+//! let mut blinq: FrameBlinq<_, u8, 4, 3, 8> = FrameBlinq::with_sink(Bank::new(pins));
+//!
+//! // Sweep back and forth, then fill up the bar
+//! blinq.enqueue(FramePattern::scan(4));
+//! blinq.enqueue(FramePattern::fill(4));
+//!
+//! for _ in 0..10 {
+//!     blinq.step();
+//! }
+//! assert!(blinq.idle());
+//! ```
+
+use crate::{ArrayPattern, Blinq, Sink, StepValue};
+
+/// The state of every pin in a bank, for a single step
+///
+/// This is implemented for `u8`, `u16`, and `u32`. The frame with every
+/// pin off is [`StepValue::OFF`].
+pub trait Frame: StepValue {
+    /// Is the given pin on?
+    fn pin(&self, pin: usize) -> bool;
+
+    /// This frame, with the given pin turned on
+    fn with_pin(self, pin: usize) -> Self;
+}
+
+/// A frame pattern, with one frame per step
+///
+/// Up to `L` steps can be stored, anything past that is truncated.
+pub type FramePattern<F, const L: usize> = ArrayPattern<F, L>;
+
+impl<F: Frame, const L: usize> ArrayPattern<F, L> {
+    /// Create a pattern that uses all `L` frames
+    pub const fn from_frames(frames: [F; L]) -> Self {
+        Self::from_values(frames)
+    }
+
+    /// A single lit pin, moving from the first of `pins` to the last
+    pub fn chase(pins: usize) -> Self {
+        let mut pat = Self::new();
+        for pin in 0..pins.min(L) {
+            pat = pat.push(F::OFF.with_pin(pin));
+        }
+        pat
+    }
+
+    /// A single lit pin, moving from the first of `pins` to the last and
+    /// back again, without repeating the ends
+    pub fn scan(pins: usize) -> Self {
+        let mut pat = Self::chase(pins);
+        for pin in (1..pins.saturating_sub(1)).rev() {
+            pat = pat.push(F::OFF.with_pin(pin));
+        }
+        pat
+    }
+
+    /// A bar growing from the first pin until all `pins` are lit
+    pub fn fill(pins: usize) -> Self {
+        let mut pat = Self::new();
+        let mut frame = F::OFF;
+        for pin in 0..pins.min(L) {
+            frame = frame.with_pin(pin);
+            pat = pat.push(frame);
+        }
+        pat
+    }
+}
+
+macro_rules! frame_impls {
+    ($($word:ty),+) => {
+        $(
+            impl Frame for $word {
+                fn pin(&self, pin: usize) -> bool {
+                    pin < <$word>::BITS as usize && (self >> pin) & 1 == 1
+                }
+
+                fn with_pin(self, pin: usize) -> Self {
+                    if pin < <$word>::BITS as usize {
+                        self | (1 << pin)
+                    } else {
+                        self
+                    }
+                }
+            }
+        )+
+    };
+}
+
+frame_impls!(u8, u16, u32);
+
+/// A bank of `P` on/off outputs, used as a [`Sink`] for frames
+///
+/// Each output is a `Sink<bool>`, like a GPIO wrapped in a `Gpio`, which
+/// also sets whether it is active-low.
+pub struct Bank<G, const P: usize> {
+    pins: [G; P],
+}
+
+impl<G, const P: usize> Bank<G, P>
+where
+    G: Sink<bool>,
+{
+    /// Wrap an array of outputs
+    pub fn new(pins: [G; P]) -> Self {
+        Self { pins }
+    }
+
+    /// Release the outputs
+    pub fn release(self) -> [G; P] {
+        self.pins
+    }
+}
+
+impl<G, F, const P: usize> Sink<F> for Bank<G, P>
+where
+    G: Sink<bool>,
+    F: Frame,
+{
+    type Error = G::Error;
+
+    /// Set every output, even if an earlier one fails. The first error is
+    /// returned.
+    fn set(&mut self, frame: F) -> Result<(), G::Error> {
+        let mut result = Ok(());
+        for (i, pin) in self.pins.iter_mut().enumerate() {
+            let res = pin.set(frame.pin(i));
+            if result.is_ok() {
+                result = res;
+            }
+        }
+        result
+    }
+}
+
+/// A queue of frame patterns, played on a bank of `P` outputs
+///
+/// This is a [`Blinq`] with a [`Bank`] output, holding up to `N - 1`
+/// patterns of up to `L` frames.
+pub type FrameBlinq<G, F, const P: usize, const N: usize, const L: usize> =
+    Blinq<Bank<G, P>, N, FramePattern<F, L>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{FakeGpio, Gpio};
+
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn helpers() {
+        let chase = FramePattern::<u8, 3>::chase(8);
+        assert_eq!(chase, FramePattern::from_frames([1, 2, 4]));
+
+        let scan = FramePattern::<u16, 8>::scan(4);
+        let frames: [u16; 6] = [1, 2, 4, 8, 4, 2];
+        assert_eq!(scan.steps(), 6);
+        assert!(frames.iter().enumerate().all(|(i, f)| scan.get(i) == *f));
+
+        // Truncated to L
+        let fill = FramePattern::<u32, 2>::fill(8);
+        assert_eq!(fill, FramePattern::from_frames([0b1, 0b11]));
+
+        assert!(0x8000u16.pin(15));
+        assert!(!0xFFu8.pin(8));
+    }
+
+    #[test]
+    fn frames() {
+        static STATES: [AtomicBool; 3] = [
+            AtomicBool::new(true),
+            AtomicBool::new(true),
+            AtomicBool::new(true),
+        ];
+        let pins = [
+            Gpio::new(FakeGpio { state: &STATES[0] }, false),
+            Gpio::new(FakeGpio { state: &STATES[1] }, false),
+            Gpio::new(FakeGpio { state: &STATES[2] }, false),
+        ];
+        let mut stepr: FrameBlinq<Gpio<FakeGpio>, u8, 3, 3, 4> =
+            FrameBlinq::with_sink(Bank::new(pins));
+        let frame = || {
+            STATES.iter().enumerate().fold(0u8, |f, (i, s)| {
+                f | (u8::from(s.load(Ordering::SeqCst)) << i)
+            })
+        };
+        assert_eq!(frame(), 0);
+
+        stepr.enqueue(FramePattern::scan(3));
+        stepr.enqueue(FramePattern::from_frames([0b101, 0b010, 0b111, 0b000]));
+        for exp in [
+            0b001, 0b010, 0b100, 0b010, 0b101, 0b010, 0b111, 0b000, 0b000,
+        ] {
+            stepr.step();
+            assert_eq!(frame(), exp);
+        }
+        assert!(stepr.idle());
+    }
+}
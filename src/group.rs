@@ -0,0 +1,243 @@
+//! Stepping several queues together
+//!
+//! A [`BlinqGroup`] owns a tuple or array of anything that implements
+//! [`Step`], like a [`Blinq`](crate::Blinq),
+//! [`PwmBlinq`](crate::pwm::PwmBlinq), or
+//! [`BlinqConsumer`](crate::BlinqConsumer), and steps them all with one
+//! call.
+//!
+//! ## Example
+//!
+//! ```rust
+//! # let led_1 = core::cell::Cell::new(false);
+//! # let led_2 = core::cell::Cell::new(false);
+//! #
+//! use blinq::{group::BlinqGroup, patterns, Blinq};
+//!
+//! let sos: Blinq<_, 4> = Blinq::with_sink(|on: bool| led_1.set(on));
+//! let hello: Blinq<_, 8> = Blinq::with_sink(|on: bool| led_2.set(on));
+//! let mut group = BlinqGroup::new((sos, hello));
+//!
+//! group.members_mut().0.enqueue(patterns::morse::SOS);
+//! group.members_mut().1.enqueue(patterns::morse::H);
+//! group.members_mut().1.enqueue(patterns::morse::I);
+//!
+//! while !group.idle() {
+//!     group.step();
+//! }
+//! ```
+
+use core::convert::Infallible;
+
+use crate::Step;
+
+/// A collection of things that are stepped together by a [`BlinqGroup`]
+///
+/// This is implemented for tuples of up to eight [`Step`]s, which may
+/// each be of a different type, and for arrays of `Step`s.
+pub trait Members {
+    /// Move every member one step, discarding any errors
+    ///
+    /// If `hold` is true, members that are not playing a pattern, but have
+    /// one waiting in their queue, are not stepped. They show their
+    /// inactive value instead.
+    fn step_members(&mut self, hold: bool);
+
+    /// Is every member idle?
+    fn members_idle(&self) -> bool;
+
+    /// Is any member partway through playing a pattern?
+    fn members_playing(&self) -> bool;
+}
+
+fn step_member<S: Step>(member: &mut S, hold: bool) {
+    if !hold || member.playing() || member.idle() {
+        member.try_step().ok();
+    } else {
+        member.try_hold().ok();
+    }
+}
+
+macro_rules! tuple_members {
+    ($($name:ident . $idx:tt),+) => {
+        impl<$($name: Step),+> Members for ($($name,)+) {
+            fn step_members(&mut self, hold: bool) {
+                $(step_member(&mut self.$idx, hold);)+
+            }
+
+            fn members_idle(&self) -> bool {
+                true $(&& self.$idx.idle())+
+            }
+
+            fn members_playing(&self) -> bool {
+                false $(|| self.$idx.playing())+
+            }
+        }
+    };
+}
+
+tuple_members!(A.0);
+tuple_members!(A.0, B.1);
+tuple_members!(A.0, B.1, C.2);
+tuple_members!(A.0, B.1, C.2, D.3);
+tuple_members!(A.0, B.1, C.2, D.3, E.4);
+tuple_members!(A.0, B.1, C.2, D.3, E.4, F.5);
+tuple_members!(A.0, B.1, C.2, D.3, E.4, F.5, G.6);
+tuple_members!(A.0, B.1, C.2, D.3, E.4, F.5, G.6, H.7);
+
+impl<S: Step, const K: usize> Members for [S; K] {
+    fn step_members(&mut self, hold: bool) {
+        for member in self.iter_mut() {
+            step_member(member, hold);
+        }
+    }
+
+    fn members_idle(&self) -> bool {
+        self.iter().all(Step::idle)
+    }
+
+    fn members_playing(&self) -> bool {
+        self.iter().any(Step::playing)
+    }
+}
+
+/// A group of queues that are stepped together
+pub struct BlinqGroup<T> {
+    members: T,
+    syncing: bool,
+}
+
+impl<T> BlinqGroup<T>
+where
+    T: Members,
+{
+    /// Create a new group from a tuple or array of members
+    pub fn new(members: T) -> Self {
+        Self {
+            members,
+            syncing: false,
+        }
+    }
+
+    /// Access the members, for example to enqueue patterns
+    pub fn members(&self) -> &T {
+        &self.members
+    }
+
+    /// Access the members, for example to enqueue patterns
+    pub fn members_mut(&mut self) -> &mut T {
+        &mut self.members
+    }
+
+    /// Release the members, consuming the group
+    pub fn release(self) -> T {
+        self.members
+    }
+
+    /// Start the next patterns of every member on the same step
+    ///
+    /// Members that finish their current pattern early are held, showing
+    /// their inactive value, until every member has finished. Then all
+    /// members move on together. Members that have nothing queued keep
+    /// stepping as usual. Note that a member looping a pattern forever
+    /// will hold the group until the loop is cancelled.
+    pub fn sync(&mut self) {
+        self.syncing = true;
+    }
+
+    /// Move every member one step
+    ///
+    /// Errors from individual members are discarded.
+    pub fn step(&mut self) {
+        if self.syncing && !self.members.members_playing() {
+            self.syncing = false;
+        }
+        self.members.step_members(self.syncing);
+    }
+
+    /// Is every member of the group idle?
+    pub fn idle(&self) -> bool {
+        self.members.members_idle()
+    }
+}
+
+impl<T> Step for BlinqGroup<T>
+where
+    T: Members,
+{
+    type Error = Infallible;
+
+    fn try_step(&mut self) -> Result<(), Infallible> {
+        BlinqGroup::step(self);
+        Ok(())
+    }
+
+    fn idle(&self) -> bool {
+        BlinqGroup::idle(self)
+    }
+
+    fn playing(&self) -> bool {
+        self.members.members_playing()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pwm::{tests::FakePwm, PwmBlinq};
+    use crate::tests::{FakeGpio, Gpio};
+    use crate::{Blinq, Pattern};
+
+    use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+
+    #[test]
+    fn group() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        static DUTY: AtomicU16 = AtomicU16::new(0);
+        let blinq: Blinq<Gpio<FakeGpio>, 3> = Blinq::new(FakeGpio { state: &STATE }, false);
+        let pwm: PwmBlinq<FakePwm, 3, 4> = PwmBlinq::new(FakePwm { duty: &DUTY }, false);
+        let mut group = BlinqGroup::new((blinq, pwm));
+        let states = || (STATE.load(Ordering::SeqCst), DUTY.load(Ordering::SeqCst));
+
+        group.members_mut().0.enqueue(Pattern::from_u32(0b1110, 4));
+        group.members_mut().1.enqueue(Pattern::from_u32(0b1, 1));
+        group.step();
+        assert_eq!(states(), (true, 1000));
+
+        // The PWM output is held off until the pattern on the GPIO is done
+        group.sync();
+        group.members_mut().0.enqueue(Pattern::from_u32(0b10, 2));
+        group.members_mut().1.enqueue(Pattern::from_u32(0b10, 2));
+        let expected = [
+            (true, 0),
+            (true, 0),
+            (false, 0),
+            (true, 1000),
+            (false, 0),
+            (false, 0),
+        ];
+        for (i, exp) in expected.iter().enumerate() {
+            group.step();
+            assert_eq!(states(), *exp, "step {}", i);
+        }
+        assert!(group.idle());
+    }
+
+    #[test]
+    fn array() {
+        static STATE_1: AtomicBool = AtomicBool::new(false);
+        static STATE_2: AtomicBool = AtomicBool::new(false);
+        let mut group: BlinqGroup<[Blinq<Gpio<FakeGpio>, 3>; 2]> = BlinqGroup::new([
+            Blinq::new(FakeGpio { state: &STATE_1 }, false),
+            Blinq::new(FakeGpio { state: &STATE_2 }, false),
+        ]);
+
+        group.members_mut()[1].enqueue(Pattern::from_u32(0b01, 2));
+        group.step();
+        assert!(!group.idle());
+        group.step();
+        assert!(!STATE_1.load(Ordering::SeqCst));
+        assert!(STATE_2.load(Ordering::SeqCst));
+        assert!(group.idle());
+    }
+}
@@ -1,11 +1,13 @@
 //! blinq - a blinking queue
 //!
 //! A queue for toggling a GPIO or blinking an LED, with binary patterns
-//! encoded as a u32.
+//! encoded as a u32, or as an array of u32s for longer patterns.
 //!
 //! ## Example
 //!
 //! ```rust
+//! # #[cfg(feature = "eh0")]
+//! # fn main() {
 //! # use core::sync::atomic::{AtomicBool, Ordering};
 //! # use embedded_hal::digital::v2::OutputPin;
 //! #
@@ -28,12 +30,12 @@
 //! # static STATE: AtomicBool = AtomicBool::new(false);
 //! # let gpio = FakeGpio { state: &STATE };
 //! #
-//! use blinq::{Pattern, Blinq, patterns};
+//! use blinq::{Pattern, Blinq, Gpio, patterns};
 //!
 //! // Create a blink queue with room for 8 patterns, that is active-low.
 //! // Note that the queue size must be one larger than the amount of patterns
 //! // that you wish to store!
-//! let mut blinq: Blinq<FakeGpio, 9> = Blinq::new(gpio, true);
+//! let mut blinq: Blinq<Gpio<FakeGpio>, 9> = Blinq::new(gpio, true);
 //!
 //! // Insert "HELLO." in morse code
 //!
@@ -52,20 +54,285 @@
 //! // The queue is now exhausted, and the GPIO will be driven to the
 //! // inactive state
 //! blinq.step();
+//! # }
+//! # #[cfg(not(feature = "eh0"))]
+//! # fn main() {}
+//! ```
+//!
+//! ## embedded-hal versions
+//!
+//! GPIOs implementing the `digital::v2::OutputPin` trait from embedded-hal
+//! 0.2 are driven with a [`Gpio`], which needs the `eh0` feature. This is
+//! enabled by default. GPIOs implementing the `digital::OutputPin` trait
+//! from embedded-hal 1.0 are driven with a `Gpio1`, which needs the `eh1`
+//! feature:
+//!
+//! ```toml
+//! blinq = { version = "0.2", features = ["eh1"] }
 //! ```
+//!
+//! Both can be enabled at once. The error type returned by
+//! [`Blinq::try_step`] is the `Error` type of the pin, which is
+//! `OutputPin::Error` for 0.2, and `ErrorType::Error` for 1.0.
+//!
+//! ## Other outputs
+//!
+//! A [`Blinq`] drives anything that implements [`Sink`]. GPIOs are wrapped
+//! in a [`Gpio`] or `Gpio1` by [`Blinq::new`], and any `FnMut(bool)`
+//! closure is also a `Sink`, see [`Blinq::with_sink`].
+//!
+//! Steps don't have to be on or off. Any [`Sequence`] can be queued, as
+//! long as the `Sink` takes its [`Sequence::Value`]. The [`pwm`], [`rgb`],
+//! and [`frames`] modules use this for brightness levels, colors, and
+//! banks of LEDs.
 
 #![cfg_attr(not(test), no_std)]
 
-use embedded_hal::digital::v2::OutputPin;
+use core::convert::Infallible;
 
-use heapless::spsc::Queue;
+use heapless::spsc::{Consumer, Queue};
 
+pub mod frames;
+pub mod group;
 pub mod patterns;
+pub mod pixels;
+pub mod pwm;
+pub mod rgb;
+mod split;
+pub mod status;
+#[cfg(feature = "fugit")]
+pub mod timed;
+pub mod writer;
+
+pub use split::{BlinqConsumer, BlinqProducer};
+
+/// A sequence of steps, each with a value
+///
+/// This is implemented by [`Pattern`] and [`LongPattern`] with on/off
+/// steps, and by the brightness, color, and frame patterns in the other
+/// modules. It is used by a [`Blinq`] to decide the value of its output
+/// on each step.
+pub trait Sequence {
+    /// The value of each step
+    ///
+    /// The default value is the inactive state, which is shown when
+    /// nothing is playing.
+    type Value: Copy + PartialEq + Default;
+
+    /// The number of steps in this sequence
+    fn steps(&self) -> usize;
+
+    /// The value of the given step
+    ///
+    /// `step` will always be less than `self.steps()`
+    fn get(&self, step: usize) -> Self::Value;
+}
+
+/// Something that is moved forward one step at a time, like a [`Blinq`]
+pub trait Step {
+    /// The error returned when driving the output fails
+    type Error;
+
+    /// Try to move one step
+    ///
+    /// See [`Blinq::try_step`] for details.
+    fn try_step(&mut self) -> Result<(), Self::Error>;
+
+    /// Is there nothing queued to play?
+    fn idle(&self) -> bool;
+
+    /// Is a pattern partway through playing?
+    ///
+    /// This is false at the boundary between patterns, even if more are
+    /// queued. By default, this is the same as `!self.idle()`.
+    fn playing(&self) -> bool {
+        !self.idle()
+    }
+
+    /// Try to move `n` steps at once
+    ///
+    /// See [`Blinq::try_advance`] for details. By default, this steps
+    /// `n` times.
+    fn try_advance(&mut self, n: usize) -> Result<(), Self::Error> {
+        let mut result = Ok(());
+        for _ in 0..n {
+            let res = self.try_step();
+            if result.is_ok() {
+                result = res;
+            }
+        }
+        result
+    }
+
+    /// Try to show the inactive value, without moving a step
+    ///
+    /// This is used by a [`BlinqGroup`](group::BlinqGroup) while a member
+    /// is held. By default, this does nothing.
+    fn try_hold(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Something that shows the value of each step
+///
+/// This is what a [`Blinq`] drives. For on/off patterns, `T` is `bool`,
+/// and this is implemented by [`Gpio`] and `Gpio1` for embedded-hal
+/// `OutputPin`s.
+/// It is also implemented by any `FnMut(T)` closure, and can be
+/// implemented for buzzers, motors, loggers, and so on.
+pub trait Sink<T = bool> {
+    /// The error returned when setting the value fails
+    type Error;
+
+    /// Set the value, where `true` is active for on/off patterns
+    fn set(&mut self, value: T) -> Result<(), Self::Error>;
+}
+
+impl<F, T> Sink<T> for F
+where
+    F: FnMut(T),
+{
+    type Error = Infallible;
+
+    fn set(&mut self, value: T) -> Result<(), Infallible> {
+        self(value);
+        Ok(())
+    }
+}
+
+/// A [`Sink`] that wraps a hardware driver, like a GPIO
+///
+/// This lets a [`Blinq`] be created straight from the driver with
+/// [`Blinq::new`], and give it back with [`Blinq::release`].
+pub trait Output {
+    /// The wrapped driver
+    type Driver;
+
+    /// Wrap a driver, which is low when active if `active_low` is true
+    fn wrap(driver: Self::Driver, active_low: bool) -> Self;
+
+    /// Release the driver
+    fn release(self) -> Self::Driver;
+}
+
+/// An embedded-hal 0.2 OutputPin, used as a [`Sink`]
+///
+/// This requires the `eh0` feature, which is enabled by default.
+#[cfg(feature = "eh0")]
+pub struct Gpio<G> {
+    gpio: G,
+    active_low: bool,
+}
+
+#[cfg(feature = "eh0")]
+impl<G> Gpio<G>
+where
+    G: embedded_hal::digital::v2::OutputPin,
+{
+    /// Wrap a GPIO, which is low when active if `active_low` is true
+    pub fn new(gpio: G, active_low: bool) -> Self {
+        Self { gpio, active_low }
+    }
+
+    /// Release the GPIO
+    pub fn release(self) -> G {
+        self.gpio
+    }
+}
+
+#[cfg(feature = "eh0")]
+impl<G> Sink for Gpio<G>
+where
+    G: embedded_hal::digital::v2::OutputPin,
+{
+    type Error = G::Error;
+
+    fn set(&mut self, state: bool) -> Result<(), G::Error> {
+        if state ^ self.active_low {
+            self.gpio.set_high()
+        } else {
+            self.gpio.set_low()
+        }
+    }
+}
+
+#[cfg(feature = "eh0")]
+impl<G> Output for Gpio<G>
+where
+    G: embedded_hal::digital::v2::OutputPin,
+{
+    type Driver = G;
+
+    fn wrap(gpio: G, active_low: bool) -> Self {
+        Gpio::new(gpio, active_low)
+    }
+
+    fn release(self) -> G {
+        Gpio::release(self)
+    }
+}
+
+/// An embedded-hal 1.0 OutputPin, used as a [`Sink`]
+///
+/// This requires the `eh1` feature.
+#[cfg(feature = "eh1")]
+pub struct Gpio1<G> {
+    gpio: G,
+    active_low: bool,
+}
+
+#[cfg(feature = "eh1")]
+impl<G> Gpio1<G>
+where
+    G: embedded_hal_1::digital::OutputPin,
+{
+    /// Wrap a GPIO, which is low when active if `active_low` is true
+    pub fn new(gpio: G, active_low: bool) -> Self {
+        Self { gpio, active_low }
+    }
+
+    /// Release the GPIO
+    pub fn release(self) -> G {
+        self.gpio
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<G> Sink for Gpio1<G>
+where
+    G: embedded_hal_1::digital::OutputPin,
+{
+    type Error = G::Error;
+
+    fn set(&mut self, state: bool) -> Result<(), G::Error> {
+        if state ^ self.active_low {
+            self.gpio.set_high()
+        } else {
+            self.gpio.set_low()
+        }
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<G> Output for Gpio1<G>
+where
+    G: embedded_hal_1::digital::OutputPin,
+{
+    type Driver = G;
+
+    fn wrap(gpio: G, active_low: bool) -> Self {
+        Gpio1::new(gpio, active_low)
+    }
+
+    fn release(self) -> G {
+        Gpio1::release(self)
+    }
+}
 
 /// A blinking pattern encoded as a u32
 ///
 /// These patterns are used with a Blinq
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Pattern {
     pattern: u32,
     used: u8,
@@ -135,23 +402,697 @@ impl Pattern {
     pub const fn reverse(&self) -> Pattern {
         Pattern::from_u32(self.pattern, self.used)
     }
+}
 
-    /// Step one bit in the pattern
-    fn step(&mut self) -> bool {
-        let result = self.pattern & 0b1 == 1;
-        self.pattern = self.pattern.rotate_right(1);
-        result
+impl Sequence for Pattern {
+    type Value = bool;
+
+    fn steps(&self) -> usize {
+        // `append` may produce a `used` larger than 32, the extra
+        // steps were truncated
+        usize::from(self.used.min(32))
+    }
+
+    fn get(&self, step: usize) -> bool {
+        (self.pattern >> step) & 0b1 == 1
+    }
+}
+
+/// A blinking pattern encoded as an array of `W` u32s
+///
+/// This can hold up to `W * 32` steps, and is useful for patterns that
+/// don't fit in a single [`Pattern`], like whole words in morse code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LongPattern<const W: usize> {
+    words: [u32; W],
+    used: usize,
+}
+
+impl<const W: usize> LongPattern<W> {
+    /// Create a new, empty pattern
+    pub const fn new() -> Self {
+        LongPattern {
+            words: [0; W],
+            used: 0,
+        }
+    }
+
+    /// Create a new pattern containing the steps of a [`Pattern`]
+    pub const fn from_pattern(pat: &Pattern) -> Self {
+        Self::new().append(pat)
+    }
+
+    /// Create a new pattern by appending a [`Pattern`] to this one
+    ///
+    /// Note: If the total length is larger than `W * 32`, the pattern
+    /// will be truncated.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use blinq::{LongPattern, patterns::morse};
+    ///
+    /// // "SOS" twice is 48 steps long, which needs at least two words
+    /// const SOS_SOS: LongPattern<2> = LongPattern::from_pattern(&morse::SOS)
+    ///     .append(&morse::SOS);
+    /// ```
+    pub const fn append(&self, other: &Pattern) -> Self {
+        let other_used = if other.used > 32 {
+            32
+        } else {
+            other.used as usize
+        };
+        let mut words = self.words;
+        let word = self.used / 32;
+        let shift = self.used % 32;
+
+        if word < W {
+            words[word] |= other.pattern << shift;
+        }
+        if shift != 0 && word + 1 < W {
+            words[word + 1] |= other.pattern >> (32 - shift);
+        }
+
+        let used = self.used + other_used;
+        LongPattern {
+            words,
+            used: if used > W * 32 { W * 32 } else { used },
+        }
+    }
+
+    /// Create a new pattern by appending another `LongPattern` to this one
+    ///
+    /// Note: If the total length is larger than `W * 32`, the pattern
+    /// will be truncated.
+    pub const fn append_long(&self, other: &LongPattern<W>) -> Self {
+        let mut out = LongPattern {
+            words: self.words,
+            used: self.used,
+        };
+        let mut i = 0;
+        while i < W && i * 32 < other.used {
+            let remain = other.used - i * 32;
+            let chunk = Pattern {
+                pattern: other.words[i],
+                used: if remain > 32 { 32 } else { remain as u8 },
+            };
+            out = out.append(&chunk);
+            i += 1;
+        }
+        out
+    }
+}
+
+impl<const W: usize> Default for LongPattern<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const W: usize> From<Pattern> for LongPattern<W> {
+    fn from(pat: Pattern) -> Self {
+        Self::from_pattern(&pat)
+    }
+}
+
+impl<const W: usize> Sequence for LongPattern<W> {
+    type Value = bool;
+
+    fn steps(&self) -> usize {
+        self.used
+    }
+
+    fn get(&self, step: usize) -> bool {
+        (self.words[step / 32] >> (step % 32)) & 0b1 == 1
+    }
+}
+
+/// A value shown by each step of an [`ArrayPattern`]
+///
+/// This is implemented for `u8`, `u16`, `u32`, and [`Color`](rgb::Color).
+pub trait StepValue: Copy + PartialEq + Default {
+    /// The inactive value, which is the same as `Default::default()`
+    ///
+    /// This is a constant, so that patterns can be built in a `const fn`.
+    const OFF: Self;
+}
+
+macro_rules! step_value_impls {
+    ($($word:ty),+) => {
+        $(
+            impl StepValue for $word {
+                const OFF: Self = 0;
+            }
+        )+
+    };
+}
+
+step_value_impls!(u8, u16, u32);
+
+/// A pattern with one value per step, stored in an array
+///
+/// Up to `L` steps can be stored, anything past that is truncated. This
+/// is used for the brightness levels of a [`LevelPattern`](pwm::LevelPattern),
+/// the colors of a [`ColorPattern`](rgb::ColorPattern), and the frames of
+/// a [`FramePattern`](frames::FramePattern).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArrayPattern<T, const L: usize> {
+    values: [T; L],
+    used: usize,
+}
+
+impl<T: StepValue, const L: usize> ArrayPattern<T, L> {
+    /// Create a new, empty, pattern
+    pub const fn new() -> Self {
+        ArrayPattern {
+            values: [T::OFF; L],
+            used: 0,
+        }
+    }
+
+    /// Create a pattern that uses all `L` values
+    pub const fn from_values(values: [T; L]) -> Self {
+        ArrayPattern { values, used: L }
+    }
+
+    /// Create a pattern that shows one value for `steps` steps
+    pub const fn solid(value: T, steps: usize) -> Self {
+        ArrayPattern {
+            values: [value; L],
+            used: if steps < L { steps } else { L },
+        }
+    }
+
+    /// Create new pattern by appending one to the other
+    ///
+    /// Note: If the combined length is greater than `L`, the pattern
+    /// will be truncated.
+    pub const fn append(&self, other: &ArrayPattern<T, L>) -> Self {
+        let mut out = ArrayPattern {
+            values: self.values,
+            used: self.used,
+        };
+        let mut i = 0;
+        while i < other.used {
+            out = out.push(other.values[i]);
+            i += 1;
+        }
+        out
+    }
+
+    /// The number of steps in this pattern
+    pub const fn steps(&self) -> usize {
+        self.used
+    }
+
+    /// The value of the given step
+    ///
+    /// Steps past the end of the pattern are off.
+    pub const fn get(&self, step: usize) -> T {
+        if step < self.used {
+            self.values[step]
+        } else {
+            T::OFF
+        }
+    }
+
+    /// Add a step to the end, unless the pattern is full
+    const fn push(mut self, value: T) -> Self {
+        if self.used < L {
+            self.values[self.used] = value;
+            self.used += 1;
+        }
+        self
+    }
+}
+
+impl<T: StepValue, const L: usize> Sequence for ArrayPattern<T, L> {
+    type Value = T;
+
+    fn steps(&self) -> usize {
+        self.used
+    }
+
+    fn get(&self, step: usize) -> T {
+        ArrayPattern::get(self, step)
+    }
+}
+
+impl<T: StepValue, const L: usize> Default for ArrayPattern<T, L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How many times a queued pattern should be played
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repeat {
+    /// Play the pattern this many times in a row
+    Times(usize),
+
+    /// Play the pattern in a loop, see [`Blinq::enqueue_loop`]
+    Forever,
+}
+
+impl Repeat {
+    /// Combine two repeat counts of the same pattern
+    fn merge(self, other: Repeat) -> Repeat {
+        match (self, other) {
+            (Repeat::Times(a), Repeat::Times(b)) => Repeat::Times(a.saturating_add(b)),
+            _ => Repeat::Forever,
+        }
+    }
+}
+
+/// An identifier given to a pattern when it is enqueued
+///
+/// Tags are chosen by the application, and do not need to be unique.
+/// They are reported in the [`Events`] of each step.
+pub type Tag = u32;
+
+/// What happened during a step of a [`Blinq`]
+///
+/// Only [tagged](Blinq::enqueue_tagged) patterns report starting and
+/// finishing. A pattern removed with [`Blinq::skip`] or [`Blinq::clear`]
+/// never finishes. If several tagged patterns start or finish during a
+/// single [`Blinq::advance`], only the last of each is reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Events {
+    /// A tagged pattern played its first step
+    pub started: Option<Tag>,
+
+    /// A tagged pattern played its last step, including all repeats
+    pub finished: Option<Tag>,
+
+    /// The last pattern finished, and nothing is left in the queue.
+    /// The idle pattern, if any, is played from the next step.
+    pub drained: bool,
+}
+
+/// What a [`Blinq`] does when a pattern is enqueued while the queue is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Discard the new pattern. This is the default.
+    #[default]
+    DropNewest,
+
+    /// Discard the oldest pattern waiting in the queue to make room
+    /// for the new pattern
+    DropOldest,
+
+    /// Merge a new pattern that is identical to the last pattern in the
+    /// queue into it, playing it one more time rather than using another
+    /// slot. This is done even if the queue is not full. If the queue is
+    /// full and the pattern can't be merged, it is discarded.
+    Coalesce,
+}
+
+/// Do two sequences produce the same steps?
+fn same_steps<P: Sequence>(a: &P, b: &P) -> bool {
+    a.steps() == b.steps() && (0..a.steps()).all(|i| a.get(i) == b.get(i))
+}
+
+/// Wakes the async driver of a split Blinq when a pattern is enqueued
+#[cfg(feature = "async")]
+type Signal =
+    embassy_sync::signal::Signal<embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex, ()>;
+
+/// A pattern in the queue, along with how many times to play it
+struct Entry<P> {
+    pat: P,
+    repeat: Repeat,
+    tag: Option<Tag>,
+}
+
+/// Somewhere the playback state can take queued patterns from
+///
+/// This is either the whole queue of a [`Blinq`], or the consumer half
+/// of a split Blinq.
+trait Source<P> {
+    fn dequeue(&mut self) -> Option<Entry<P>>;
+    fn is_empty(&self) -> bool;
+    fn len(&self) -> usize;
+
+    /// The queued entries that can be seen, in order
+    ///
+    /// This may be fewer than `len()` entries.
+    fn entries<'a>(&'a self) -> impl Iterator<Item = &'a Entry<P>>
+    where
+        P: 'a;
+}
+
+impl<P, const N: usize> Source<P> for Queue<Entry<P>, N> {
+    fn dequeue(&mut self) -> Option<Entry<P>> {
+        Queue::dequeue(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        Queue::is_empty(self)
+    }
+
+    fn len(&self) -> usize {
+        Queue::len(self)
+    }
+
+    fn entries<'a>(&'a self) -> impl Iterator<Item = &'a Entry<P>>
+    where
+        P: 'a,
+    {
+        self.iter()
+    }
+}
+
+impl<P, const N: usize> Source<P> for Consumer<'_, Entry<P>, N> {
+    fn dequeue(&mut self) -> Option<Entry<P>> {
+        Consumer::dequeue(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.ready()
+    }
+
+    fn len(&self) -> usize {
+        Consumer::len(self)
+    }
+
+    // Only the head of the queue can be seen from the consumer side
+    fn entries<'a>(&'a self) -> impl Iterator<Item = &'a Entry<P>>
+    where
+        P: 'a,
+    {
+        self.peek().into_iter()
+    }
+}
+
+/// The first step in `start..pat.steps()` that is not `level`, if any
+fn find_edge<P: Sequence>(pat: &P, start: usize, level: P::Value) -> Option<usize> {
+    (start..pat.steps()).find(|i| pat.get(*i) != level)
+}
+
+/// The playback state of a Blinq
+///
+/// This is kept apart from the queue, so it can be borrowed separately
+/// when a Blinq is split.
+struct Core<S, P: Sequence> {
+    current: Option<Entry<P>>,
+    step: usize,
+    paused: Option<(Entry<P>, usize)>,
+    idle_pattern: Option<P>,
+    idle_step: usize,
+    level: P::Value,
+    events: Events,
+    sink: S,
+}
+
+impl<S, P> Core<S, P>
+where
+    S: Sink<P::Value>,
+    P: Sequence,
+{
+    fn new(mut sink: S) -> Self {
+        sink.set(P::Value::default()).ok();
+
+        Self {
+            current: None,
+            step: 0,
+            paused: None,
+            idle_pattern: None,
+            idle_step: 0,
+            level: P::Value::default(),
+            events: Events::default(),
+            sink,
+        }
+    }
+
+    fn idle(&self, queue: &impl Source<P>) -> bool {
+        !self.playing() && queue.is_empty()
+    }
+
+    fn playing(&self) -> bool {
+        self.current.is_some() || self.paused.is_some()
+    }
+
+    /// Is there nothing to play, not even an idle pattern?
+    #[cfg(feature = "async")]
+    fn quiescent(&self, queue: &impl Source<P>) -> bool {
+        self.idle(queue) && self.idle_pattern.is_none()
+    }
+
+    fn try_hold(&mut self) -> Result<(), S::Error> {
+        self.level = P::Value::default();
+        self.sink.set(self.level)
+    }
+
+    fn cancel_loop(&mut self) {
+        self.cancel_loop_where(|_| true);
+    }
+
+    /// Cancel the loops for which `f` returns true, given their tag
+    fn cancel_loop_where(&mut self, f: impl Fn(Option<Tag>) -> bool) {
+        // The loop may be paused by an interrupt
+        let paused = self.paused.as_mut().map(|(entry, _)| entry);
+        for entry in self.current.as_mut().into_iter().chain(paused) {
+            if entry.repeat == Repeat::Forever && f(entry.tag) {
+                entry.repeat = Repeat::Times(1);
+            }
+        }
+    }
+
+    fn interrupt(&mut self, pat: P) {
+        if pat.steps() == 0 {
+            return;
+        }
+
+        if let Some(current) = self.current.take() {
+            if self.paused.is_none() {
+                self.paused = Some((current, self.step));
+            }
+        }
+
+        self.current = Some(Entry {
+            pat,
+            repeat: Repeat::Times(1),
+            tag: None,
+        });
+        self.step = 0;
+        self.idle_step = 0;
+    }
+
+    fn skip(&mut self) {
+        self.current = None;
+        self.step = 0;
+    }
+
+    /// Is the current pattern, if any, between two cycles?
+    fn between_cycles(&self) -> bool {
+        self.step == 0
+    }
+
+    fn set_idle_pattern(&mut self, pat: Option<P>) {
+        self.idle_pattern = pat;
+        self.idle_step = 0;
+    }
+
+    fn try_step(&mut self, queue: &mut impl Source<P>) -> Result<Events, S::Error> {
+        self.try_advance(1, queue)
+    }
+
+    fn try_advance(&mut self, n: usize, queue: &mut impl Source<P>) -> Result<Events, S::Error> {
+        self.events = Events::default();
+        if n == 0 {
+            return Ok(self.events);
+        }
+
+        let busy = !self.idle(queue);
+        let state = self.advance(n, queue);
+        self.level = state;
+        self.events.drained = busy && self.idle(queue);
+
+        // Drive the output. This should be last, in case errors occur
+        self.sink.set(state)?;
+        Ok(self.events)
+    }
+
+    fn next_edge(&self, queue: &impl Source<P>) -> Option<usize> {
+        let level = self.level;
+        let mut offset = 0usize;
+        let hidden = queue.len() > queue.entries().count();
+
+        // The rest of the current pattern, then any pattern it interrupted
+        let playing = self
+            .current
+            .as_ref()
+            .map(|entry| (entry, self.step))
+            .into_iter()
+            .chain(self.paused.as_ref().map(|(entry, step)| (entry, *step)));
+
+        // Then everything waiting in the queue, from the start
+        let queued = queue.entries().map(|entry| (entry, 0));
+
+        // The idle pattern restarts after any pattern is played
+        let mut restart = false;
+
+        let mut entries = playing.chain(queued).peekable();
+        while let Some((entry, start)) = entries.next() {
+            let len = entry.pat.steps();
+            if len == 0 || entry.repeat == Repeat::Times(0) {
+                continue;
+            }
+            restart = true;
+
+            // The rest of this cycle
+            if let Some(i) = find_edge(&entry.pat, start, level) {
+                return Some(offset.saturating_add(i - start + 1));
+            }
+            offset = offset.saturating_add(len - start);
+
+            // Any further cycles, which are all the same
+            let cycles = match entry.repeat {
+                Repeat::Times(times) => times - 1,
+                Repeat::Forever if entries.peek().is_none() && !hidden => {
+                    // Loops forever, unless something else is enqueued
+                    return find_edge(&entry.pat, 0, level).map(|i| offset.saturating_add(i + 1));
+                }
+                Repeat::Forever => 0,
+            };
+            if cycles != 0 {
+                if let Some(i) = find_edge(&entry.pat, 0, level) {
+                    return Some(offset.saturating_add(i + 1));
+                }
+                offset = offset.saturating_add(len.saturating_mul(cycles));
+            }
+        }
+
+        // Some of the queue can't be seen, check again once we get there
+        if hidden {
+            return Some(offset.max(1));
+        }
+
+        // Then the idle pattern, forever
+        match self.idle_pattern.as_ref() {
+            Some(pat) if pat.steps() != 0 => {
+                let len = pat.steps();
+                let idle_step = if restart { 0 } else { self.idle_step };
+                (0..len)
+                    .find(|i| pat.get((idle_step + i) % len) != level)
+                    .map(|i| offset.saturating_add(i + 1))
+            }
+            _ if level != P::Value::default() => Some(offset.saturating_add(1)),
+            _ => None,
+        }
+    }
+
+    /// Load the next pattern to play, if none is currently active
+    fn load(&mut self, queue: &mut impl Source<P>) {
+        if self.current.is_some() {
+            return;
+        }
+
+        // Resume a pattern paused by an interrupt
+        if let Some((entry, step)) = self.paused.take() {
+            self.current = Some(entry);
+            self.step = step;
+            return;
+        }
+
+        while let Some(entry) = queue.dequeue() {
+            // Only take non-empty patterns
+            if entry.pat.steps() != 0 && entry.repeat != Repeat::Times(0) {
+                if entry.tag.is_some() {
+                    self.events.started = entry.tag;
+                }
+                self.current = Some(entry);
+                self.idle_step = 0;
+                break;
+            }
+        }
+    }
+
+    /// Move `n` steps forward, returning the state of the last step
+    ///
+    /// This takes a constant number of iterations per pattern, skipping
+    /// over whole cycles of repeated or looping patterns at once.
+    fn advance(&mut self, mut n: usize, queue: &mut impl Source<P>) -> P::Value {
+        let mut state = P::Value::default();
+
+        while n != 0 {
+            self.load(queue);
+
+            let mut entry = match self.current.take() {
+                Some(entry) => entry,
+                None => {
+                    // Nothing left to play, the rest of the steps are idle
+                    return match self.idle_pattern.as_ref() {
+                        Some(pat) if pat.steps() != 0 => {
+                            let len = pat.steps();
+                            self.idle_step = (self.idle_step + n % len) % len;
+                            pat.get((self.idle_step + len - 1) % len)
+                        }
+                        _ => P::Value::default(),
+                    };
+                }
+            };
+
+            // Walk within the current cycle of the pattern
+            let len = entry.pat.steps();
+            let left = len - self.step;
+            if n < left {
+                self.step += n;
+                state = entry.pat.get(self.step - 1);
+                self.current = Some(entry);
+                break;
+            }
+
+            // We have exhausted this pattern, reset our step counter to zero,
+            // and decide whether to play it again. Whole cycles that would be
+            // played again are skipped.
+            n -= left;
+            self.step = 0;
+            state = entry.pat.get(len - 1);
+
+            let again = match entry.repeat {
+                Repeat::Times(times) if times > 1 => {
+                    let cycles = (n / len).min(times - 1);
+                    n -= cycles * len;
+                    entry.repeat = Repeat::Times(times - 1 - cycles);
+                    times - 1 - cycles != 0
+                }
+                // Loops only yield once something else is waiting
+                Repeat::Forever if queue.is_empty() => {
+                    n %= len;
+                    true
+                }
+                _ => false,
+            };
+
+            if again {
+                self.current = Some(entry);
+            } else if entry.tag.is_some() {
+                self.events.finished = entry.tag;
+            }
+        }
+
+        state
     }
 }
 
 /// A blinking queue
 ///
 /// This takes an embedded-hal OutputPin, and drives it based on
-/// given patterns on each step.
+/// given patterns on each step. Other outputs can be driven by
+/// implementing [`Sink`], and creating the Blinq with
+/// [`Blinq::with_sink`].
+///
+/// By default, a Blinq holds [`Pattern`]s. To hold longer patterns, use
+/// a [`LongPattern`] as `P`. Regular `Pattern`s can still be enqueued,
+/// and are converted on the way in. Any other [`Sequence`] can be used
+/// as `P`, with a [`Sink`] for its values.
 ///
 /// ## Example
 ///
 /// ```rust
+/// # #[cfg(feature = "eh0")]
+/// # fn main() {
 /// # use core::sync::atomic::{AtomicBool, Ordering};
 /// # use embedded_hal::digital::v2::OutputPin;
 /// #
@@ -174,12 +1115,12 @@ impl Pattern {
 /// # static STATE: AtomicBool = AtomicBool::new(false);
 /// # let gpio = FakeGpio { state: &STATE };
 /// #
-/// use blinq::{Pattern, Blinq, patterns};
+/// use blinq::{Pattern, Blinq, Gpio, patterns};
 ///
 /// // Create a blink queue with room for 8 patterns, that is active-low
 /// // Note that the queue size must be one larger than the amount of patterns
 /// // that you wish to store!
-/// let mut blinq: Blinq<FakeGpio, 9> = Blinq::new(gpio, true);
+/// let mut blinq: Blinq<Gpio<FakeGpio>, 9> = Blinq::new(gpio, true);
 ///
 /// // Insert "HELLO." in morse code
 ///
@@ -198,162 +1139,641 @@ impl Pattern {
 /// // The queue is now exhausted, and the GPIO will be driven to the
 /// // inactive state
 /// blinq.step();
+/// # }
+/// # #[cfg(not(feature = "eh0"))]
+/// # fn main() {}
 /// ```
-pub struct Blinq<G, const N: usize>
+pub struct Blinq<S, const N: usize, P = Pattern>
 where
-    G: OutputPin,
+    S: Sink<P::Value>,
+    P: Sequence,
 {
-    current: Option<Pattern>,
-    queue: Queue<Pattern, N>,
-    step: u8,
-    gpio: G,
-    active_low: bool,
+    queue: Queue<Entry<P>, N>,
+    core: Core<S, P>,
+    overflow: Overflow,
+    dropped: usize,
+    #[cfg(feature = "async")]
+    signal: Signal,
 }
 
-impl<G, const N: usize> Blinq<G, N>
+impl<S, const N: usize, P> Blinq<S, N, P>
 where
-    G: OutputPin,
+    S: Sink<P::Value>,
+    P: Sequence,
 {
     /// Create a new Blinq with the given GPIO
     ///
-    /// The GPIO will be driven to the "inactive" state
-    /// on creation
-    pub fn new(mut gpio: G, active_low: bool) -> Self {
-        if active_low {
-            gpio.set_high().ok();
-        } else {
-            gpio.set_low().ok();
+    /// The GPIO will be driven to the "inactive" state
+    /// on creation. It is wrapped in the [`Output`] given
+    /// by the type of the Blinq, like [`Gpio`], `Gpio1`
+    /// or [`Pwm`](pwm::Pwm).
+    pub fn new(gpio: S::Driver, active_low: bool) -> Self
+    where
+        S: Output,
+    {
+        Self::with_sink(S::wrap(gpio, active_low))
+    }
+
+    /// Consume the queue, returning the gpio
+    ///
+    /// Note: The gpio will be in whatever the last state
+    /// was, which may be active or inactive
+    pub fn release(self) -> S::Driver
+    where
+        S: Output,
+    {
+        self.into_sink().release()
+    }
+
+    /// Create a new Blinq with the given output
+    ///
+    /// The output will be set to the "inactive" state
+    /// on creation
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use blinq::{Blinq, patterns};
+    ///
+    /// // Print each step, instead of driving a GPIO
+    /// let mut blinq: Blinq<_, 4> = Blinq::with_sink(|on: bool| {
+    ///     println!("{}", if on { "on" } else { "off" });
+    /// });
+    /// blinq.enqueue(patterns::blinks::SHORT_ON_OFF);
+    /// blinq.step();
+    /// ```
+    pub fn with_sink(sink: S) -> Self {
+        Self {
+            queue: Queue::new(),
+            core: Core::new(sink),
+            overflow: Overflow::DropNewest,
+            dropped: 0,
+            #[cfg(feature = "async")]
+            signal: Signal::new(),
+        }
+    }
+
+    /// Consume the queue, returning the output
+    pub fn into_sink(self) -> S {
+        self.core.sink
+    }
+
+    /// Access the output
+    pub fn sink(&self) -> &S {
+        &self.core.sink
+    }
+
+    /// Access the output, for example to change its settings
+    ///
+    /// Changes take effect on the next step.
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.core.sink
+    }
+
+    /// Split the Blinq into a producer and a consumer
+    ///
+    /// The [`BlinqProducer`] can enqueue patterns, and the [`BlinqConsumer`]
+    /// steps the queue and drives the GPIO. They can be used from different
+    /// contexts, for example enqueueing from an interrupt while stepping from
+    /// the main loop, without any locking.
+    ///
+    /// The producer always discards new patterns if the queue is full,
+    /// regardless of the [overflow policy](Blinq::set_overflow), but these
+    /// are still counted by [`Blinq::dropped`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # let led = core::cell::Cell::new(false);
+    /// #
+    /// use blinq::{Blinq, patterns};
+    ///
+    /// let mut blinq: Blinq<_, 4> = Blinq::with_sink(|on: bool| led.set(on));
+    /// let (mut producer, mut consumer) = blinq.split();
+    ///
+    /// // e.g. in a button interrupt
+    /// producer.enqueue(patterns::blinks::SHORT_ON_OFF);
+    ///
+    /// // e.g. in a timer interrupt
+    /// consumer.step();
+    /// ```
+    pub fn split(&mut self) -> (BlinqProducer<'_, N, P>, BlinqConsumer<'_, S, N, P>) {
+        let (producer, consumer) = self.queue.split();
+        (
+            BlinqProducer::new(
+                producer,
+                &mut self.dropped,
+                #[cfg(feature = "async")]
+                &self.signal,
+            ),
+            BlinqConsumer::new(
+                consumer,
+                &mut self.core,
+                #[cfg(feature = "async")]
+                &self.signal,
+            ),
+        )
+    }
+
+    /// Set what happens when a pattern is enqueued while the queue is full
+    ///
+    /// This applies to [`Blinq::enqueue`], [`Blinq::enqueue_repeat`], and
+    /// [`Blinq::enqueue_loop`]. The `try_` variants always return the new
+    /// pattern if the queue is full. The default is [`Overflow::DropNewest`].
+    pub fn set_overflow(&mut self, policy: Overflow) {
+        self.overflow = policy;
+    }
+
+    /// The number of patterns that have been discarded because the queue
+    /// was full
+    ///
+    /// This counter wraps on overflow.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Enqueue a new pattern into the queue
+    ///
+    /// If the queue is currently full, the [overflow policy](Blinq::set_overflow)
+    /// is applied. By default, the new pattern will be discarded.
+    pub fn enqueue(&mut self, pat: impl Into<P>) {
+        self.enqueue_entry(pat.into(), Repeat::Times(1), None);
+    }
+
+    /// Try to enqueue a new pattern into the queue
+    ///
+    /// If the queue is currently full, an error will be returned
+    pub fn try_enqueue(&mut self, pat: impl Into<P>) -> Result<(), P> {
+        self.try_enqueue_entry(pat.into(), Repeat::Times(1), None)
+    }
+
+    /// Enqueue a new pattern, which will be played `n` times in a row
+    ///
+    /// The pattern only takes a single slot in the queue. If the queue is
+    /// currently full, the [overflow policy](Blinq::set_overflow) is applied.
+    pub fn enqueue_repeat(&mut self, pat: impl Into<P>, n: usize) {
+        self.enqueue_entry(pat.into(), Repeat::Times(n), None);
+    }
+
+    /// Try to enqueue a new pattern, which will be played `n` times in a row
+    ///
+    /// The pattern only takes a single slot in the queue. If the queue is
+    /// currently full, an error will be returned.
+    pub fn try_enqueue_repeat(&mut self, pat: impl Into<P>, n: usize) -> Result<(), P> {
+        self.try_enqueue_entry(pat.into(), Repeat::Times(n), None)
+    }
+
+    /// Enqueue a new pattern, which will be played in a loop
+    ///
+    /// The pattern will keep playing until another pattern is enqueued,
+    /// or the loop is cancelled with [`Blinq::cancel_loop`]. The current
+    /// cycle of the loop is always finished first. While looping, the
+    /// Blinq is not [idle](Blinq::idle).
+    ///
+    /// If the queue is currently full, the [overflow policy](Blinq::set_overflow)
+    /// is applied.
+    pub fn enqueue_loop(&mut self, pat: impl Into<P>) {
+        self.enqueue_entry(pat.into(), Repeat::Forever, None);
+    }
+
+    /// Try to enqueue a new pattern, which will be played in a loop
+    ///
+    /// See [`Blinq::enqueue_loop`] for details. If the queue is currently
+    /// full, an error will be returned.
+    pub fn try_enqueue_loop(&mut self, pat: impl Into<P>) -> Result<(), P> {
+        self.try_enqueue_entry(pat.into(), Repeat::Forever, None)
+    }
+
+    /// Enqueue a new pattern with a tag, played `repeat` times
+    ///
+    /// The tag is reported in the [`Events`] returned by [`Blinq::step`]
+    /// when the pattern starts and finishes playing. If the queue is
+    /// currently full, the [overflow policy](Blinq::set_overflow) is applied.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use blinq::{Blinq, Repeat, patterns};
+    ///
+    /// const SAVING: u32 = 1;
+    ///
+    /// let mut blinq: Blinq<_, 4> = Blinq::with_sink(|_on: bool| {});
+    /// blinq.enqueue_tagged(patterns::blinks::SHORT_ON_OFF, Repeat::Times(2), SAVING);
+    ///
+    /// assert_eq!(blinq.step().started, Some(SAVING));
+    /// blinq.advance(2);
+    ///
+    /// let events = blinq.step();
+    /// assert_eq!(events.finished, Some(SAVING));
+    /// assert!(events.drained);
+    /// ```
+    pub fn enqueue_tagged(&mut self, pat: impl Into<P>, repeat: Repeat, tag: Tag) {
+        self.enqueue_entry(pat.into(), repeat, Some(tag));
+    }
+
+    /// Try to enqueue a new pattern with a tag, played `repeat` times
+    ///
+    /// See [`Blinq::enqueue_tagged`] for details. If the queue is currently
+    /// full, an error will be returned.
+    pub fn try_enqueue_tagged(
+        &mut self,
+        pat: impl Into<P>,
+        repeat: Repeat,
+        tag: Tag,
+    ) -> Result<(), P> {
+        self.try_enqueue_entry(pat.into(), repeat, Some(tag))
+    }
+
+    /// Stop the currently playing loop
+    ///
+    /// The current cycle of the loop will be finished before moving
+    /// on to the next pattern. This also applies to a loop paused by an
+    /// [interrupt](Blinq::interrupt).
+    pub fn cancel_loop(&mut self) {
+        self.core.cancel_loop();
+    }
+
+    /// Stop a playing or paused loop with the given tag
+    pub(crate) fn cancel_tagged_loop(&mut self, tag: Tag) {
+        self.core.cancel_loop_where(|t| t == Some(tag));
+    }
+
+    /// Play a pattern immediately, interrupting the current pattern
+    ///
+    /// The current pattern is paused, and will resume from the same step
+    /// once the interrupting pattern has finished. The interrupting pattern
+    /// does not use a slot in the queue.
+    ///
+    /// If another interrupting pattern is already playing, it is replaced,
+    /// and the originally paused pattern will still be resumed afterwards.
+    pub fn interrupt(&mut self, pat: impl Into<P>) {
+        self.core.interrupt(pat.into());
+    }
+
+    /// Remove all patterns waiting in the queue
+    ///
+    /// The currently playing pattern is not affected, see [`Blinq::skip`].
+    pub fn clear(&mut self) {
+        while self.queue.dequeue().is_some() {}
+    }
+
+    /// Abort the currently playing pattern
+    ///
+    /// The next pattern will be started on the next step. If the current
+    /// pattern is an [interrupt](Blinq::interrupt), the paused pattern
+    /// will be resumed.
+    pub fn skip(&mut self) {
+        self.core.skip();
+    }
+
+    /// The next pattern waiting in the queue, if any
+    pub fn peek(&self) -> Option<&P> {
+        self.queue.peek().map(|entry| &entry.pat)
+    }
+
+    /// Has the current pattern not played any steps of its current cycle?
+    pub(crate) fn between_cycles(&self) -> bool {
+        self.core.between_cycles()
+    }
+
+    /// The tag of the currently playing pattern, if it has one
+    ///
+    /// While an [interrupt](Blinq::interrupt) is playing, this is `None`.
+    pub fn playing_tag(&self) -> Option<Tag> {
+        self.core.current.as_ref().and_then(|entry| entry.tag)
+    }
+
+    /// Keep only the queued patterns for which `f` returns true
+    ///
+    /// `f` is called with each pattern waiting in the queue, in order, along
+    /// with its tag. The order of the kept patterns is not changed. The
+    /// currently playing pattern is not affected, see [`Blinq::skip`].
+    ///
+    /// This is not available on a split Blinq.
+    pub fn retain(&mut self, mut f: impl FnMut(&P, Option<Tag>) -> bool) {
+        // The queue can only be taken from the front, so rotate every
+        // entry through it once, leaving out the ones we don't want
+        for _ in 0..self.queue.len() {
+            if let Some(entry) = self.queue.dequeue() {
+                if f(&entry.pat, entry.tag) {
+                    // A slot was just freed, so this can't fail
+                    self.queue.enqueue(entry).ok();
+                }
+            }
+        }
+    }
+
+    /// Remove every queued pattern with the given tag
+    ///
+    /// Returns the number of patterns removed. The currently playing
+    /// pattern is not affected, see [`Blinq::playing_tag`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use blinq::{Blinq, Repeat, patterns::blinks};
+    ///
+    /// const CONNECTING: u32 = 1;
+    /// const CONNECTED: u32 = 2;
+    ///
+    /// let mut blinq: Blinq<_, 4> = Blinq::with_sink(|_on: bool| {});
+    /// blinq.enqueue(blinks::LONG_ON_OFF);
+    /// blinq.enqueue_tagged(blinks::SHORT_ON_OFF, Repeat::Forever, CONNECTING);
+    ///
+    /// // The connection succeeded before we got to it
+    /// assert_eq!(blinq.remove_tagged(CONNECTING), 1);
+    /// blinq.enqueue_tagged(blinks::MEDIUM_ON_OFF, Repeat::Times(1), CONNECTED);
+    /// assert_eq!(blinq.len(), 2);
+    /// ```
+    pub fn remove_tagged(&mut self, tag: Tag) -> usize {
+        let before = self.queue.len();
+        self.retain(|_, t| t != Some(tag));
+        before - self.queue.len()
+    }
+
+    /// Replace every queued pattern with the given tag, in place
+    ///
+    /// The replaced patterns keep their position in the queue, their
+    /// repeat count, and their tag. Returns the number of patterns
+    /// replaced. The currently playing pattern is not affected.
+    pub fn replace_tagged(&mut self, tag: Tag, pat: impl Into<P>) -> usize
+    where
+        P: Clone,
+    {
+        let pat = pat.into();
+        let mut replaced = 0;
+        for entry in self.queue.iter_mut().filter(|e| e.tag == Some(tag)) {
+            entry.pat = pat.clone();
+            replaced += 1;
         }
+        replaced
+    }
 
-        Self {
-            current: None,
-            queue: Queue::new(),
-            step: 0,
-            gpio,
-            active_low,
-        }
+    /// The number of patterns waiting in the queue
+    ///
+    /// This does not include the currently playing pattern.
+    pub fn len(&self) -> usize {
+        self.queue.len()
     }
 
-    /// Consume the queue, returning the gpio
+    /// Are there no patterns waiting in the queue?
     ///
-    /// Note: The gpio will be in whatever the last state
-    /// was, which may be active or inactive
-    pub fn release(self) -> G {
-        self.gpio
+    /// Unlike [`Blinq::idle`], this ignores the currently playing pattern.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
     }
 
-    /// Enqueue a new pattern into the queue
+    /// The total number of patterns the queue can hold
     ///
-    /// If the queue is currently full, the pattern will be discarded
-    pub fn enqueue(&mut self, pat: Pattern) {
-        self.queue.enqueue(pat).ok();
+    /// This is one less than `N`.
+    pub fn capacity(&self) -> usize {
+        self.queue.capacity()
     }
 
-    /// Try to enqueue a new pattern into the queue
+    /// The number of patterns that can be enqueued before the queue is full
+    pub fn remaining(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Set a pattern to be played whenever the queue is empty
     ///
-    /// If the queue is currently full, an error will be returned
-    pub fn try_enqueue(&mut self, pat: Pattern) -> Result<(), Pattern> {
-        self.queue.enqueue(pat)
+    /// The idle pattern loops until a new pattern is enqueued, which
+    /// will interrupt it immediately. Once the queue is empty again, the
+    /// idle pattern restarts from the beginning. While the idle pattern
+    /// is playing, the Blinq is still considered [idle](Blinq::idle).
+    pub fn set_idle_pattern(&mut self, pat: impl Into<P>) {
+        self.core.set_idle_pattern(Some(pat.into()));
+    }
+
+    /// Remove the idle pattern, if any
+    ///
+    /// The GPIO will be driven to the inactive state when the queue is empty.
+    pub fn clear_idle_pattern(&mut self) {
+        self.core.set_idle_pattern(None);
+    }
+
+    fn enqueue_entry(&mut self, pat: P, repeat: Repeat, tag: Option<Tag>) {
+        if self.overflow == Overflow::Coalesce {
+            if let Some(last) = self.queue.iter_mut().last() {
+                if last.tag == tag && same_steps(&last.pat, &pat) {
+                    last.repeat = last.repeat.merge(repeat);
+                    return;
+                }
+            }
+        }
+
+        let entry = match self.queue.enqueue(Entry { pat, repeat, tag }) {
+            Ok(()) => return,
+            Err(entry) => entry,
+        };
+
+        if self.overflow == Overflow::DropOldest {
+            self.queue.dequeue();
+            self.queue.enqueue(entry).ok();
+        }
+        self.dropped = self.dropped.wrapping_add(1);
+    }
+
+    fn try_enqueue_entry(&mut self, pat: P, repeat: Repeat, tag: Option<Tag>) -> Result<(), P> {
+        self.queue
+            .enqueue(Entry { pat, repeat, tag })
+            .map_err(|entry| entry.pat)
     }
 
     /// Move the queue one step
     ///
     /// This will update the GPIO with the next state in the current
     /// pattern, or start the next pattern. If the queue is empty,
-    /// the GPIO will be driven to the inactive state.
+    /// the idle pattern will be played, or the GPIO will be driven to
+    /// the inactive state if there is none.
     ///
     /// If any GPIO errors occur, they will be discarded, but the
-    /// pattern will still step forward.
+    /// pattern will still step forward. The [`Events`] of the step are
+    /// returned.
     ///
     /// blinq has no concept of time, so you should call it at a rate
     /// that makes sense for you. For example, if you wanted the pattern
     /// `0b101010` to be a 1hz blink, you should call `step` every 500ms.
     /// If you want `0b11110000` to be a 1hz blink, you should call `step`
     /// every 125ms.
-    pub fn step(&mut self) {
+    pub fn step(&mut self) -> Events {
         let _ = self.try_step();
+        self.events()
+    }
+
+    /// The [`Events`] of the last step, or the last call to [`Blinq::advance`]
+    ///
+    /// These are kept even if driving the GPIO failed.
+    pub fn events(&self) -> Events {
+        self.core.events
     }
 
     /// Is the queue empty?
     pub fn idle(&self) -> bool {
-        self.current.is_none() && self.queue.peek().is_none()
+        self.core.idle(&self.queue)
+    }
+
+    /// Move the queue `n` steps at once
+    ///
+    /// This has the same result as calling [`Blinq::step`] `n` times, but
+    /// the GPIO is only updated once, with the state of the last step. This
+    /// is useful to catch up after missing steps, for example after sleeping.
+    /// Whole cycles of repeated or looping patterns are skipped at once, so
+    /// this takes a constant time per pattern rather than per step.
+    ///
+    /// If `n` is zero, nothing happens. If any GPIO errors occur, they will
+    /// be discarded. The [`Events`] of all `n` steps are returned.
+    pub fn advance(&mut self, n: usize) -> Events {
+        let _ = self.try_advance(n);
+        self.events()
+    }
+
+    /// Try to move the queue `n` steps at once
+    ///
+    /// See [`Blinq::advance`] for details. If any GPIO errors occur, they
+    /// will be returned, but the pattern will still move forward.
+    pub fn try_advance(&mut self, n: usize) -> Result<Events, S::Error> {
+        self.core.try_advance(n, &mut self.queue)
+    }
+
+    /// The number of steps until the GPIO next changes state
+    ///
+    /// This looks through the rest of the current pattern, the queue, and
+    /// the idle pattern. `None` means the GPIO will not change until
+    /// something else is enqueued, for example when the queue is empty.
+    ///
+    /// This can be used with [`Blinq::advance`] to only wake up when the
+    /// GPIO needs to change, rather than on every step.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # let led = core::cell::Cell::new(false);
+    /// #
+    /// use blinq::{Blinq, Pattern};
+    ///
+    /// let mut blinq: Blinq<_, 4> = Blinq::with_sink(|on: bool| led.set(on));
+    /// blinq.enqueue(Pattern::from_u32(0b0011_1100, 8));
+    ///
+    /// // Only three wakeups are needed for this pattern
+    /// while let Some(steps) = blinq.next_edge() {
+    ///     // Sleep for `steps` step periods here, then
+    ///     blinq.advance(steps);
+    /// }
+    /// ```
+    pub fn next_edge(&self) -> Option<usize> {
+        self.core.next_edge(&self.queue)
     }
 
     /// Try to move the queue one step
     ///
     /// This will update the GPIO with the next state in the current
     /// pattern, or start the next pattern. If the queue is empty,
-    /// the GPIO will be driven to the inactive state.
+    /// the idle pattern will be played, or the GPIO will be driven to
+    /// the inactive state if there is none.
     ///
     /// If any GPIO errors occur, they will be returned, but the
-    /// pattern will still step forward.
+    /// pattern will still step forward. The events of the step can still
+    /// be read with [`Blinq::events`]. Otherwise, the [`Events`] of the
+    /// step are returned.
     ///
     /// blinq has no concept of time, so you should call it at a rate
     /// that makes sense for you. For example, if you wanted the pattern
     /// `0b101010` to be a 1hz blink, you should call `step` every 500ms.
     /// If you want `0b11110000` to be a 1hz blink, you should call `step`
     /// every 125ms.
-    pub fn try_step(&mut self) -> Result<(), G::Error> {
-        // Attempt to load a pattern if none is currently active
-        if self.current.is_none() {
-            while let Some(pat) = self.queue.dequeue() {
-                // Only take non-empty patterns
-                if pat.used != 0 {
-                    self.current = Some(pat);
-                    break;
-                }
-            }
-        }
+    pub fn try_step(&mut self) -> Result<Events, S::Error> {
+        self.core.try_step(&mut self.queue)
+    }
+}
 
-        let state = match self.current.take() {
-            None => {
-                // No pattern, drive GPIO inactive
-                false
-            }
-            Some(mut pat) => {
-                // TODO: remove this after Rust >= 1.46 where we can truncate lens
-                // in const-fns
-                pat.used = pat.used.min(32);
-
-                // Walk step and counter
-                let state = pat.step();
-                self.step += 1;
-
-                // If we have exhausted this pattern, reset our step counter to zero.
-                // Otherwise, return the pattern to current.
-                if self.step >= pat.used {
-                    self.step = 0;
-                } else {
-                    self.current = Some(pat);
-                }
+impl<S, const N: usize, P> Step for Blinq<S, N, P>
+where
+    S: Sink<P::Value>,
+    P: Sequence,
+{
+    type Error = S::Error;
 
-                state
-            }
-        };
+    fn try_step(&mut self) -> Result<(), S::Error> {
+        Blinq::try_step(self).map(drop)
+    }
 
-        // Drive the GPIO. This should be last, in case errors occur
-        if state ^ self.active_low {
-            self.gpio.set_high()?;
-        } else {
-            self.gpio.set_low()?;
-        }
+    fn idle(&self) -> bool {
+        Blinq::idle(self)
+    }
 
-        Ok(())
+    fn playing(&self) -> bool {
+        self.core.playing()
+    }
+
+    fn try_advance(&mut self, n: usize) -> Result<(), S::Error> {
+        Blinq::try_advance(self, n).map(drop)
+    }
+
+    fn try_hold(&mut self) -> Result<(), S::Error> {
+        self.core.try_hold()
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
     use crate::patterns::morse::SOS;
 
     use core::sync::atomic::{AtomicBool, Ordering};
 
-    struct FakeGpio {
-        state: &'static AtomicBool,
+    // The tests use whichever GPIO adapter is enabled
+    #[cfg(feature = "eh0")]
+    pub(crate) use crate::Gpio;
+    #[cfg(all(feature = "eh1", not(feature = "eh0")))]
+    pub(crate) use crate::Gpio1 as Gpio;
+
+    pub(crate) struct FakeGpio {
+        pub(crate) state: &'static AtomicBool,
+    }
+
+    // Without either, a stand-in drives the fake GPIO directly
+    #[cfg(not(any(feature = "eh0", feature = "eh1")))]
+    pub(crate) struct Gpio<G> {
+        gpio: G,
+        active_low: bool,
+    }
+
+    #[cfg(not(any(feature = "eh0", feature = "eh1")))]
+    impl Gpio<FakeGpio> {
+        pub(crate) fn new(gpio: FakeGpio, active_low: bool) -> Self {
+            Self { gpio, active_low }
+        }
+    }
+
+    #[cfg(not(any(feature = "eh0", feature = "eh1")))]
+    impl Sink for Gpio<FakeGpio> {
+        type Error = ();
+
+        fn set(&mut self, on: bool) -> Result<(), ()> {
+            self.gpio
+                .state
+                .store(on ^ self.active_low, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[cfg(not(any(feature = "eh0", feature = "eh1")))]
+    impl Output for Gpio<FakeGpio> {
+        type Driver = FakeGpio;
+
+        fn wrap(gpio: FakeGpio, active_low: bool) -> Self {
+            Gpio::new(gpio, active_low)
+        }
+
+        fn release(self) -> FakeGpio {
+            self.gpio
+        }
     }
 
-    impl OutputPin for FakeGpio {
+    #[cfg(feature = "eh0")]
+    impl embedded_hal::digital::v2::OutputPin for FakeGpio {
         type Error = ();
         fn set_low(&mut self) -> Result<(), ()> {
             self.state.store(false, Ordering::SeqCst);
@@ -365,11 +1785,49 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "eh1")]
+    impl embedded_hal_1::digital::ErrorType for FakeGpio {
+        type Error = core::convert::Infallible;
+    }
+
+    #[cfg(feature = "eh1")]
+    impl embedded_hal_1::digital::OutputPin for FakeGpio {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.state.store(false, Ordering::SeqCst);
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.state.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[cfg(all(feature = "eh0", feature = "eh1"))]
+    #[test]
+    fn both_hals() {
+        static EH0: AtomicBool = AtomicBool::new(false);
+        static EH1: AtomicBool = AtomicBool::new(false);
+        let mut eh0: Blinq<Gpio<FakeGpio>, 2> = Blinq::new(FakeGpio { state: &EH0 }, true);
+        let mut eh1: Blinq<Gpio1<FakeGpio>, 2> = Blinq::new(FakeGpio { state: &EH1 }, false);
+        assert!(EH0.load(Ordering::SeqCst));
+        assert!(!EH1.load(Ordering::SeqCst));
+
+        eh0.enqueue(Pattern::from_u32(0b1, 1));
+        eh1.enqueue(Pattern::from_u32(0b1, 1));
+        eh0.step();
+        eh1.step();
+        assert!(!EH0.load(Ordering::SeqCst));
+        assert!(EH1.load(Ordering::SeqCst));
+
+        let _: FakeGpio = eh0.release();
+        let _: FakeGpio = eh1.release();
+    }
+
     #[test]
     fn simple() {
         static STATE: AtomicBool = AtomicBool::new(false);
         let fg = FakeGpio { state: &STATE };
-        let mut stepr: Blinq<FakeGpio, 2> = Blinq::new(fg, false);
+        let mut stepr: Blinq<Gpio<FakeGpio>, 2> = Blinq::new(fg, false);
         stepr.enqueue(SOS);
 
         stepr.step();
@@ -428,7 +1886,7 @@ mod tests {
     fn queued() {
         static STATE: AtomicBool = AtomicBool::new(false);
         let fg = FakeGpio { state: &STATE };
-        let mut stepr: Blinq<FakeGpio, 4> = Blinq::new(fg, false);
+        let mut stepr: Blinq<Gpio<FakeGpio>, 4> = Blinq::new(fg, false);
         stepr.enqueue(SOS);
         stepr.enqueue(SOS);
         stepr.enqueue(SOS);
@@ -486,4 +1944,460 @@ mod tests {
             assert_eq!(STATE.load(Ordering::SeqCst), false);
         }
     }
+
+    #[test]
+    fn long_pattern() {
+        use crate::patterns::morse::{O, S};
+
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut stepr: Blinq<Gpio<FakeGpio>, 3, LongPattern<3>> = Blinq::new(fg, false);
+
+        // 72 steps, crossing two word boundaries
+        let sos3 = LongPattern::<3>::from_pattern(&SOS)
+            .append(&SOS)
+            .append(&SOS);
+        assert_eq!(sos3.steps(), 72);
+        stepr.enqueue(sos3);
+        stepr.enqueue(O);
+
+        let expected = LongPattern::<3>::new()
+            .append(&S)
+            .append(&O)
+            .append(&S)
+            .append_long(&LongPattern::from_pattern(&SOS).append(&SOS))
+            .append(&O);
+        assert_eq!(expected.steps(), 84);
+
+        for i in 0..expected.steps() {
+            stepr.step();
+            assert_eq!(STATE.load(Ordering::SeqCst), expected.get(i));
+        }
+
+        assert!(stepr.idle());
+    }
+
+    #[test]
+    fn long_pattern_truncates() {
+        let pat = LongPattern::<1>::from_pattern(&SOS).append(&SOS);
+        assert_eq!(pat.steps(), 32);
+        for i in 0..32 {
+            assert_eq!(pat.get(i), SOS.get(i % SOS.steps()));
+        }
+    }
+
+    fn assert_steps<S: Sink, const N: usize, P: Sequence<Value = bool>>(
+        blinq: &mut Blinq<S, N, P>,
+        state: &AtomicBool,
+        expected: &[u8],
+    ) {
+        for (i, exp) in expected.iter().enumerate() {
+            blinq.step();
+            assert_eq!(state.load(Ordering::SeqCst), *exp == 1, "step {}", i);
+        }
+    }
+
+    #[test]
+    fn repeat() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut stepr: Blinq<Gpio<FakeGpio>, 4> = Blinq::new(fg, false);
+        stepr.enqueue_repeat(Pattern::from_u32(0b10, 2), 3);
+        stepr.enqueue_repeat(Pattern::from_u32(0b1, 1), 0);
+        stepr.enqueue(Pattern::from_u32(0b11, 2));
+
+        assert_steps(&mut stepr, &STATE, &[1, 0, 1, 0, 1, 0, 1, 1, 0]);
+        assert!(stepr.idle());
+    }
+
+    #[test]
+    fn looping() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut stepr: Blinq<Gpio<FakeGpio>, 3> = Blinq::new(fg, false);
+        stepr.enqueue_loop(Pattern::from_u32(0b100, 3));
+
+        assert_steps(&mut stepr, &STATE, &[1, 0, 0, 1, 0, 0, 1]);
+        assert!(!stepr.idle());
+
+        // The current cycle is finished before moving on
+        stepr.enqueue(Pattern::from_u32(0b11, 2));
+        assert_steps(&mut stepr, &STATE, &[0, 0, 1, 1, 0]);
+        assert!(stepr.idle());
+
+        stepr.enqueue_loop(Pattern::from_u32(0b10, 2));
+        assert_steps(&mut stepr, &STATE, &[1, 0, 1]);
+        stepr.cancel_loop();
+        assert_steps(&mut stepr, &STATE, &[0, 0]);
+        assert!(stepr.idle());
+    }
+
+    #[test]
+    fn idle_pattern() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut stepr: Blinq<Gpio<FakeGpio>, 3> = Blinq::new(fg, false);
+        stepr.set_idle_pattern(Pattern::from_u32(0b100, 3));
+
+        assert_steps(&mut stepr, &STATE, &[1, 0, 0, 1, 0]);
+        assert!(stepr.idle());
+
+        // A new pattern interrupts the idle pattern immediately
+        stepr.enqueue(Pattern::from_u32(0b011, 3));
+        assert!(!stepr.idle());
+        assert_steps(&mut stepr, &STATE, &[0, 1, 1]);
+
+        // Then the idle pattern starts over
+        assert_steps(&mut stepr, &STATE, &[1, 0, 0, 1]);
+
+        stepr.clear_idle_pattern();
+        assert_steps(&mut stepr, &STATE, &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn interrupt() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut stepr: Blinq<Gpio<FakeGpio>, 3> = Blinq::new(fg, false);
+        stepr.enqueue(Pattern::from_u32(0b110011, 6));
+        stepr.enqueue(Pattern::from_u32(0b1, 1));
+
+        assert_steps(&mut stepr, &STATE, &[1, 1, 0]);
+
+        // Resumes from the fourth step after the interrupt
+        stepr.interrupt(Pattern::from_u32(0b101, 3));
+        assert_steps(&mut stepr, &STATE, &[1, 0, 1]);
+        assert_steps(&mut stepr, &STATE, &[0, 1, 1, 1, 0]);
+        assert!(stepr.idle());
+
+        // Interrupting while idle just plays the pattern
+        stepr.interrupt(Pattern::from_u32(0b1, 1));
+        assert!(!stepr.idle());
+        assert_steps(&mut stepr, &STATE, &[1, 0]);
+        assert!(stepr.idle());
+
+        // Cancelling a loop paused by an interrupt
+        stepr.enqueue_loop(Pattern::from_u32(0b10, 2));
+        assert_steps(&mut stepr, &STATE, &[1]);
+        stepr.interrupt(Pattern::from_u32(0b1, 1));
+        stepr.cancel_loop();
+        assert_steps(&mut stepr, &STATE, &[1, 0, 0]);
+        assert!(stepr.idle());
+    }
+
+    #[test]
+    fn management() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut stepr: Blinq<Gpio<FakeGpio>, 4> = Blinq::new(fg, false);
+        assert_eq!(stepr.capacity(), 3);
+
+        stepr.enqueue(Pattern::from_u32(0b11, 2));
+        stepr.enqueue(Pattern::from_u32(0b10, 2));
+        stepr.enqueue(Pattern::from_u32(0b01, 2));
+        assert_eq!(stepr.len(), 3);
+        assert_eq!(stepr.remaining(), 0);
+
+        assert_steps(&mut stepr, &STATE, &[1]);
+        assert_eq!(stepr.len(), 2);
+        assert_eq!(stepr.peek(), Some(&Pattern::from_u32(0b10, 2)));
+
+        stepr.skip();
+        assert_steps(&mut stepr, &STATE, &[1]);
+        assert_eq!(stepr.peek(), Some(&Pattern::from_u32(0b01, 2)));
+
+        stepr.clear();
+        assert!(stepr.is_empty());
+        assert_eq!(stepr.peek(), None);
+        assert!(!stepr.idle());
+        assert_steps(&mut stepr, &STATE, &[0, 0]);
+        assert!(stepr.idle());
+    }
+
+    #[test]
+    fn overflow() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut stepr: Blinq<Gpio<FakeGpio>, 3> = Blinq::new(fg, false);
+        let on = Pattern::from_u32(0b1, 1);
+        let off = Pattern::from_u32(0b0, 1);
+
+        stepr.enqueue(on.clone());
+        stepr.enqueue(off.clone());
+        stepr.enqueue(off.clone());
+        assert_eq!(stepr.dropped(), 1);
+        assert!(stepr.try_enqueue(on.clone()).is_err());
+        assert_eq!(stepr.dropped(), 1);
+
+        stepr.set_overflow(Overflow::DropOldest);
+        stepr.enqueue(on.clone());
+        assert_eq!(stepr.dropped(), 2);
+        assert_steps(&mut stepr, &STATE, &[0, 1, 0]);
+
+        stepr.set_overflow(Overflow::Coalesce);
+        stepr.enqueue(on.clone());
+        stepr.enqueue_repeat(on.clone(), 2);
+        stepr.enqueue(off.clone());
+        stepr.enqueue(off);
+        stepr.enqueue(on);
+        assert_eq!(stepr.dropped(), 3);
+        assert_eq!(stepr.len(), 2);
+        assert_steps(&mut stepr, &STATE, &[1, 1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn advance() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        static STEPPED: AtomicBool = AtomicBool::new(false);
+
+        // Every combination of advancing should match stepping one at a time
+        let patterns = [
+            (Pattern::from_u32(0b110, 3), Repeat::Times(4)),
+            (Pattern::from_u32(0b1, 1), Repeat::Times(1)),
+            (Pattern::from_u32(0b1001, 4), Repeat::Forever),
+        ];
+
+        for n in 0..40 {
+            let mut advanced: Blinq<Gpio<FakeGpio>, 4> =
+                Blinq::new(FakeGpio { state: &STATE }, false);
+            let mut stepped: Blinq<Gpio<FakeGpio>, 4> =
+                Blinq::new(FakeGpio { state: &STEPPED }, false);
+            for blinq in [&mut advanced, &mut stepped] {
+                blinq.set_idle_pattern(Pattern::from_u32(0b10000, 5));
+                for (pat, repeat) in patterns.iter() {
+                    blinq.enqueue_entry(pat.clone(), *repeat, None);
+                }
+            }
+
+            advanced.step();
+            stepped.step();
+            advanced.advance(n);
+            for _ in 0..n {
+                stepped.step();
+            }
+            stepped.cancel_loop();
+            advanced.cancel_loop();
+
+            for i in 0..20 {
+                assert_eq!(
+                    STATE.load(Ordering::SeqCst),
+                    STEPPED.load(Ordering::SeqCst),
+                    "advance {} step {}",
+                    n,
+                    i
+                );
+                advanced.step();
+                stepped.step();
+            }
+        }
+    }
+
+    #[test]
+    fn next_edge() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut stepr: Blinq<Gpio<FakeGpio>, 5> = Blinq::new(fg, false);
+        assert_eq!(stepr.next_edge(), None);
+
+        stepr.enqueue(Pattern::from_u32(0b0011, 4));
+        stepr.enqueue_repeat(Pattern::from_u32(0b1, 1), 3);
+        stepr.enqueue(Pattern::from_u32(0b1100, 4));
+        stepr.enqueue_repeat(Pattern::from_u32(0b01, 2), 2);
+
+        let mut edges = std::vec::Vec::new();
+        while let Some(n) = stepr.next_edge() {
+            let before = STATE.load(Ordering::SeqCst);
+            stepr.advance(n);
+            assert_ne!(STATE.load(Ordering::SeqCst), before);
+            edges.push(n);
+        }
+        assert_eq!(edges, [3, 7, 3, 1, 1, 1]);
+        assert!(!STATE.load(Ordering::SeqCst));
+
+        // Loops that never change never have an edge
+        stepr.enqueue_loop(Pattern::from_u32(0b11, 2));
+        assert_eq!(stepr.next_edge(), Some(1));
+        stepr.step();
+        assert_eq!(stepr.next_edge(), None);
+
+        // Until something else is waiting
+        stepr.enqueue(Pattern::from_u32(0b10, 2));
+        assert_eq!(stepr.next_edge(), Some(3));
+
+        stepr.set_idle_pattern(Pattern::from_u32(0b100, 3));
+        stepr.advance(3);
+        assert_eq!(stepr.next_edge(), Some(1));
+        stepr.step();
+        assert_eq!(stepr.next_edge(), Some(1));
+        stepr.step();
+        assert_eq!(stepr.next_edge(), Some(2));
+    }
+
+    #[test]
+    fn next_edge_idle_restart() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut stepr: Blinq<Gpio<FakeGpio>, 3> = Blinq::new(fg, false);
+
+        // The idle pattern starts again once the queued pattern is done
+        stepr.set_idle_pattern(Pattern::from_u32(0b100, 3));
+        stepr.step();
+        stepr.enqueue(Pattern::from_u32(0b1, 1));
+        assert_eq!(stepr.next_edge(), Some(3));
+        stepr.advance(2);
+        assert!(STATE.load(Ordering::SeqCst));
+        stepr.step();
+        assert!(!STATE.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn sink() {
+        struct Recorder(std::vec::Vec<bool>);
+
+        impl Sink for Recorder {
+            type Error = ();
+
+            fn set(&mut self, state: bool) -> Result<(), ()> {
+                self.0.push(state);
+                Ok(())
+            }
+        }
+
+        let mut stepr: Blinq<Recorder, 3> = Blinq::with_sink(Recorder(std::vec::Vec::new()));
+        stepr.enqueue(Pattern::from_u32(0b101, 3));
+        stepr.advance(2);
+        stepr.step();
+        stepr.step();
+        assert_eq!(stepr.into_sink().0, [false, false, true, false]);
+
+        // Closures are sinks too
+        let mut count = 0;
+        let mut stepr: Blinq<_, 3> = Blinq::with_sink(|on: bool| count += usize::from(on));
+        stepr.enqueue(Pattern::from_u32(0b1101, 4));
+        for _ in 0..5 {
+            stepr.step();
+        }
+        drop(stepr);
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn values() {
+        use crate::pwm::LevelPattern;
+
+        let mut levels = std::vec::Vec::new();
+        let mut stepr: Blinq<_, 3, LevelPattern<4>> = Blinq::with_sink(|l: u8| levels.push(l));
+        stepr.enqueue_repeat(LevelPattern::from_levels([10, 10, 20, 20]), 2);
+        stepr.enqueue(LevelPattern::from_levels([20, 30, 30, 0]));
+
+        assert_eq!(stepr.next_edge(), Some(1));
+        stepr.step();
+        assert_eq!(stepr.next_edge(), Some(2));
+        stepr.advance(4);
+        assert_eq!(stepr.next_edge(), Some(2));
+        stepr.advance(6);
+        assert_eq!(stepr.next_edge(), Some(1));
+        stepr.advance(1);
+        assert_eq!(stepr.next_edge(), None);
+        assert!(stepr.idle());
+        drop(stepr);
+        assert_eq!(levels, [0, 10, 10, 30, 0]);
+    }
+
+    #[test]
+    fn events() {
+        let mut stepr: Blinq<_, 5> = Blinq::with_sink(|_on: bool| {});
+        let started = |tag| Events {
+            started: Some(tag),
+            ..Events::default()
+        };
+        let finished = |tag| Events {
+            finished: Some(tag),
+            ..Events::default()
+        };
+
+        // Untagged patterns only report draining
+        stepr.enqueue(Pattern::from_u32(0b10, 2));
+        stepr.enqueue_tagged(Pattern::from_u32(0b1, 1), Repeat::Times(2), 7);
+        stepr.enqueue_tagged(Pattern::from_u32(0b10, 2), Repeat::Forever, 9);
+        for exp in [
+            Events::default(),
+            Events::default(),
+            started(7),
+            finished(7),
+            started(9),
+            Events::default(),
+        ] {
+            assert_eq!(stepr.step(), exp);
+        }
+
+        stepr.cancel_loop();
+        stepr.interrupt(Pattern::from_u32(0b1, 1));
+        for _ in 0..2 {
+            assert_eq!(stepr.step(), Events::default());
+        }
+        let exp = Events {
+            finished: Some(9),
+            drained: true,
+            ..Events::default()
+        };
+        assert_eq!(stepr.try_step(), Ok(exp));
+        assert_eq!(stepr.events(), exp);
+        assert_eq!(stepr.step(), Events::default());
+
+        // Only the last of each is reported when advancing
+        stepr.enqueue_tagged(Pattern::from_u32(0b11, 2), Repeat::Times(1), 1);
+        stepr.enqueue_tagged(Pattern::from_u32(0b11, 2), Repeat::Times(1), 2);
+        stepr.enqueue_tagged(Pattern::from_u32(0b11, 2), Repeat::Times(1), 3);
+        let exp = Events {
+            started: Some(2),
+            finished: Some(1),
+            drained: false,
+        };
+        assert_eq!(stepr.advance(3), exp);
+
+        // Skipped patterns never finish
+        stepr.skip();
+        assert_eq!(stepr.step(), started(3));
+        stepr.skip();
+        assert_eq!(stepr.step(), Events::default());
+        assert!(stepr.idle());
+    }
+
+    #[test]
+    fn tagged() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut stepr: Blinq<Gpio<FakeGpio>, 6> = Blinq::new(fg, false);
+        let long = LongPattern::<1>::from(Pattern::from_u32(0b1, 1));
+
+        stepr.enqueue_tagged(Pattern::from_u32(0b11, 2), Repeat::Times(1), 1);
+        stepr.enqueue_tagged(Pattern::from_u32(0b10, 2), Repeat::Forever, 2);
+        stepr.enqueue(Pattern::from_u32(0b0, 1));
+        stepr.enqueue_tagged(Pattern::from_u32(0b10, 2), Repeat::Times(3), 2);
+        stepr.enqueue_tagged(Pattern::from_u32(0b0, 3), Repeat::Times(1), 3);
+        assert_eq!(stepr.remaining(), 0);
+
+        stepr.step();
+        assert_eq!(stepr.playing_tag(), Some(1));
+        assert_eq!(stepr.remove_tagged(2), 2);
+        assert_eq!(stepr.remove_tagged(1), 0);
+        assert_eq!(stepr.replace_tagged(3, Pattern::from_u32(0b1, 1)), 1);
+        assert_eq!(stepr.len(), 2);
+
+        // Slots are freed, and the order is kept
+        stepr.enqueue_tagged(Pattern::from_u32(0b11, 2), Repeat::Times(1), 4);
+        stepr.retain(|pat, tag| tag.is_some() || pat.steps() != 1);
+        assert_eq!(stepr.len(), 2);
+        assert_steps(&mut stepr, &STATE, &[1, 1, 1, 1, 0]);
+        assert!(stepr.idle());
+        assert_eq!(stepr.playing_tag(), None);
+
+        // Replacing patterns of a different type
+        let mut stepr: Blinq<Gpio<FakeGpio>, 3, LongPattern<1>> =
+            Blinq::new(FakeGpio { state: &STATE }, false);
+        stepr.enqueue_tagged(Pattern::from_u32(0b0, 4), Repeat::Times(2), 5);
+        assert_eq!(stepr.replace_tagged(5, long), 1);
+        assert_steps(&mut stepr, &STATE, &[1, 1, 0]);
+    }
 }
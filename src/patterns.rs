@@ -4,8 +4,32 @@ pub mod morse {
     //! Morse Code Patterns
     //!
     //! * Dots are represented by `0b10`.
-    //! * Dashes are represented by `0b110`.
-    use crate::Pattern;
+    //! * Dashes are represented by `0b1110`.
+    //!
+    //! The letter constants only include the one step gap after each
+    //! element. To encode whole messages with the correct three step gap
+    //! between letters and seven step gap between words, use [`encode`]
+    //! or [`encode_long`].
+    //!
+    //! ## Example
+    //!
+    //! ```rust
+    //! use blinq::{LongPattern, patterns::morse};
+    //!
+    //! // One pattern per character, with gaps included
+    //! assert!(morse::encode("Hello world").all(|pat| pat.is_ok()));
+    //!
+    //! // Or the whole message as a single pattern
+    //! let hello: LongPattern<4> = morse::encode_long("Hello world").unwrap();
+    //!
+    //! // Unsupported characters are reported
+    //! assert_eq!(
+    //!     morse::encode_long::<4>("50%"),
+    //!     Err(morse::Error::Unsupported('%')),
+    //! );
+    //! ```
+    use crate::{LongPattern, Pattern};
+    use core::str::Chars;
 
     pub const DOT: Pattern = Pattern::from_u32(0b10, 2);
     pub const DASH: Pattern = Pattern::from_u32(0b1110, 4);
@@ -56,12 +80,192 @@ pub mod morse {
     pub const HYPHEN: Pattern = DASH.append(&DOT).append(&DOT).append(&DOT).append(&DOT).append(&DASH);
     pub const FRACTION_BAR: Pattern = DASH.append(&DOT).append(&DOT).append(&DASH).append(&DOT);
     pub const BRACKETS: Pattern = DASH.append(&DOT).append(&DASH).append(&DASH).append(&DOT).append(&DASH);
+    pub const LEFT_BRACKET: Pattern = DASH.append(&DOT).append(&DASH).append(&DASH).append(&DOT);
     pub const QUOTATION_MARK: Pattern = DOT.append(&DASH).append(&DOT).append(&DOT).append(&DASH).append(&DOT);
     pub const AT_SIGN: Pattern = DOT.append(&DASH).append(&DASH).append(&DOT).append(&DASH).append(&DOT);
     pub const EQUALS_SIGN: Pattern = DASH.append(&DOT).append(&DOT).append(&DOT).append(&DASH);
     pub const ERROR: Pattern = DOT.append(&DOT).append(&DOT).append(&DOT).append(&DOT).append(&DOT).append(&DOT).append(&DOT);
 
     pub const SOS: Pattern = S.append(&O).append(&S);
+
+    /// The extra gap after a letter, making a three step gap with the
+    /// gap after the last element
+    pub const LETTER_GAP: Pattern = Pattern::from_u32(0b00, 2);
+
+    /// The extra gap after a word, making a seven step gap with the
+    /// letter gap
+    pub const WORD_GAP: Pattern = Pattern::from_u32(0b0000, 4);
+
+    /// Errors that can occur when encoding text
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Error {
+        /// The character has no morse code representation
+        Unsupported(char),
+
+        /// The encoded text does not fit in the pattern
+        TooLong,
+    }
+
+    /// Get the pattern for a single character, ignoring case
+    ///
+    /// This does not include the letter gap. Returns `None` for whitespace
+    /// and unsupported characters.
+    pub const fn lookup(c: char) -> Option<Pattern> {
+        let pat = match c.to_ascii_uppercase() {
+            'A' => A,
+            'B' => B,
+            'C' => C,
+            'D' => D,
+            'E' => E,
+            'F' => F,
+            'G' => G,
+            'H' => H,
+            'I' => I,
+            'J' => J,
+            'K' => K,
+            'L' => L,
+            'M' => M,
+            'N' => N,
+            'O' => O,
+            'P' => P,
+            'Q' => Q,
+            'R' => R,
+            'S' => S,
+            'T' => T,
+            'U' => U,
+            'V' => V,
+            'W' => W,
+            'X' => X,
+            'Y' => Y,
+            'Z' => Z,
+            '0' => ZERO,
+            '1' => ONE,
+            '2' => TWO,
+            '3' => THREE,
+            '4' => FOUR,
+            '5' => FIVE,
+            '6' => SIX,
+            '7' => SEVEN,
+            '8' => EIGHT,
+            '9' => NINE,
+            '.' => FULL_STOP,
+            ',' => COMMA,
+            ':' => COLON,
+            '?' => QUESTION_MARK,
+            '\'' => APOSTROPHE,
+            '-' => HYPHEN,
+            '/' => FRACTION_BAR,
+            '(' => LEFT_BRACKET,
+            ')' => BRACKETS,
+            '"' => QUOTATION_MARK,
+            '@' => AT_SIGN,
+            '=' => EQUALS_SIGN,
+            _ => return None,
+        };
+        Some(pat)
+    }
+
+    /// Encode text as morse code, one pattern per character
+    ///
+    /// Each letter is followed by a letter gap, and each run of whitespace
+    /// becomes a word gap. Letters are not case sensitive.
+    pub fn encode(text: &str) -> Encoder<'_> {
+        Encoder {
+            chars: text.chars(),
+            in_space: false,
+        }
+    }
+
+    /// Encode text as morse code into a single [`LongPattern`]
+    ///
+    /// Spacing is the same as [`encode`]. If the text does not fit in
+    /// `W * 32` steps, an error is returned.
+    pub fn encode_long<const W: usize>(text: &str) -> Result<LongPattern<W>, Error> {
+        use crate::Sequence;
+
+        let mut out = LongPattern::new();
+        for pat in encode(text) {
+            let pat = pat?;
+            if out.steps() + pat.steps() > W * 32 {
+                return Err(Error::TooLong);
+            }
+            out = out.append(&pat);
+        }
+        Ok(out)
+    }
+
+    /// An iterator of morse code patterns, created by [`encode`]
+    pub struct Encoder<'a> {
+        chars: Chars<'a>,
+        in_space: bool,
+    }
+
+    impl<'a> Iterator for Encoder<'a> {
+        type Item = Result<Pattern, Error>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            loop {
+                let c = self.chars.next()?;
+                if let Some(pat) = encode_char(c, &mut self.in_space) {
+                    return Some(pat);
+                }
+            }
+        }
+    }
+
+    /// Encode a single character, including the gap after it
+    ///
+    /// `in_space` tracks whether the previous character was whitespace,
+    /// so that runs of whitespace produce a single word gap. Returns `None`
+    /// if the character produces no pattern.
+    pub(crate) fn encode_char(c: char, in_space: &mut bool) -> Option<Result<Pattern, Error>> {
+        if c.is_whitespace() {
+            // Collapse runs of whitespace into a single word gap
+            if core::mem::replace(in_space, true) {
+                return None;
+            }
+            return Some(Ok(WORD_GAP));
+        }
+
+        *in_space = false;
+        Some(match lookup(c) {
+            Some(pat) => Ok(pat.append(&LETTER_GAP)),
+            None => Err(Error::Unsupported(c)),
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::Sequence;
+
+        fn bits(pat: &LongPattern<4>) -> std::string::String {
+            (0..pat.steps())
+                .map(|i| if pat.get(i) { '1' } else { '0' })
+                .collect()
+        }
+
+        #[test]
+        fn gaps() {
+            let pat: LongPattern<4> = encode_long("Ee  t").unwrap();
+            // E, letter gap, E, word gap, T, letter gap
+            assert_eq!(bits(&pat), "100010000000111000");
+        }
+
+        #[test]
+        fn errors() {
+            assert_eq!(encode_long::<4>("e#"), Err(Error::Unsupported('#')));
+            assert_eq!(encode_long::<1>("SOS SOS"), Err(Error::TooLong));
+            assert!(encode("a b").all(|p| p.is_ok()));
+        }
+
+        #[test]
+        fn brackets() {
+            assert_eq!(lookup('('), Some(LEFT_BRACKET));
+            assert_eq!(lookup(')'), Some(BRACKETS));
+            assert_ne!(lookup('('), lookup(')'));
+        }
+    }
 }
 
 pub mod blinks {
@@ -0,0 +1,321 @@
+//! Patterns for addressable LEDs, like the WS2812 or SK6812
+//!
+//! A [`PixelBlinq`] keeps a queue of [`ColorPattern`]s for each pixel in a
+//! chain, and steps them all together. A new frame is only written to the
+//! chain when the color of at least one pixel has changed.
+//!
+//! Frames are written with the [`FrameWrite`] trait. With the `smart-leds`
+//! feature, this is implemented for drivers implementing the
+//! `SmartLedsWrite` trait from the `smart-leds` crate, with `RGB8` colors.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use blinq::{
+//!     patterns,
+//!     pixels::{FrameWrite, PixelBlinq},
+//!     rgb::{Color, ColorPattern},
+//! };
+//! # struct Driver;
+//! # impl FrameWrite for Driver {
+//! #     type Error = ();
+//! #     fn write_frame(&mut self, _frame: &[blinq::rgb::Color]) -> Result<(), ()> {
+//! #         Ok(())
+//! #     }
+//! # }
+//! # let driver = Driver;
+//!
+//! // A chain of 3 pixels, each with room for 4 patterns of up to 32 steps
+//! let mut blinq: PixelBlinq<_, 3, 5, 32> = PixelBlinq::new(driver);
+//!
+//! // SOS in red on the first pixel, and a green blink on the last
+//! blinq.enqueue(0, ColorPattern::from_pattern(&patterns::morse::SOS, Color::RED));
+//! blinq.enqueue(2, ColorPattern::from_pattern(&patterns::blinks::SHORT_ON_OFF, Color::GREEN));
+//!
+//! for _ in 0..24 {
+//!     blinq.step();
+//! }
+//! assert!(blinq.idle());
+//! ```
+
+use core::convert::Infallible;
+
+use crate::rgb::{Color, ColorPattern, RgbBlinq};
+use crate::{Sink, Step};
+
+/// Something that can show a frame of colors on a chain of pixels
+pub trait FrameWrite {
+    /// The error returned when writing the frame fails
+    type Error;
+
+    /// Write the colors of every pixel in the chain, in order
+    fn write_frame(&mut self, frame: &[Color]) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "smart-leds")]
+impl<D> FrameWrite for D
+where
+    D: smart_leds_trait::SmartLedsWrite<Color = smart_leds_trait::RGB8>,
+{
+    type Error = D::Error;
+
+    fn write_frame(&mut self, frame: &[Color]) -> Result<(), D::Error> {
+        let colors = frame
+            .iter()
+            .map(|c| smart_leds_trait::RGB8::new(c.r, c.g, c.b));
+        self.write(colors)
+    }
+}
+
+/// The output of each pixel in a [`PixelBlinq`]
+///
+/// This holds the color of the pixel until the frame is written.
+#[derive(Default)]
+pub struct Slot {
+    color: Color,
+}
+
+impl Sink<Color> for Slot {
+    type Error = Infallible;
+
+    fn set(&mut self, color: Color) -> Result<(), Infallible> {
+        self.color = color;
+        Ok(())
+    }
+}
+
+/// A queue of color patterns for each pixel in a chain of `P` pixels
+///
+/// Each pixel has its own [`RgbBlinq`], and pixels with nothing to play
+/// are turned off.
+pub struct PixelBlinq<W, const P: usize, const N: usize, const L: usize>
+where
+    W: FrameWrite,
+{
+    pixels: [RgbBlinq<Slot, N, L>; P],
+    frame: [Color; P],
+    writer: W,
+}
+
+impl<W, const P: usize, const N: usize, const L: usize> PixelBlinq<W, P, N, L>
+where
+    W: FrameWrite,
+{
+    /// Create a new PixelBlinq, and turn every pixel off
+    pub fn new(mut writer: W) -> Self {
+        let frame = [Color::OFF; P];
+        writer.write_frame(&frame).ok();
+        Self {
+            pixels: core::array::from_fn(|_| RgbBlinq::with_sink(Slot::default())),
+            frame,
+            writer,
+        }
+    }
+
+    /// Release the frame writer, consuming the PixelBlinq
+    pub fn release(self) -> W {
+        self.writer
+    }
+
+    /// Enqueue a new pattern into the queue of a pixel
+    ///
+    /// If the queue is currently full, or `pixel` is not in the chain, the
+    /// new pattern will be discarded.
+    pub fn enqueue(&mut self, pixel: usize, pat: impl Into<ColorPattern<L>>) {
+        self.try_enqueue(pixel, pat).ok();
+    }
+
+    /// Try to enqueue a new pattern into the queue of a pixel
+    ///
+    /// If the queue is currently full, or `pixel` is not in the chain, an
+    /// error will be returned.
+    pub fn try_enqueue(
+        &mut self,
+        pixel: usize,
+        pat: impl Into<ColorPattern<L>>,
+    ) -> Result<(), ColorPattern<L>> {
+        let pat = pat.into();
+        match self.pixels.get_mut(pixel) {
+            Some(blinq) => blinq.try_enqueue(pat),
+            None => Err(pat),
+        }
+    }
+
+    /// Access the queue of a pixel, for example to loop a pattern
+    ///
+    /// Returns `None` if `pixel` is not in the chain.
+    pub fn pixel_mut(&mut self, pixel: usize) -> Option<&mut RgbBlinq<Slot, N, L>> {
+        self.pixels.get_mut(pixel)
+    }
+
+    /// The colors of every pixel, as of the last step
+    pub fn frame(&self) -> &[Color; P] {
+        &self.frame
+    }
+
+    /// Move one step. If the frame is unable to be written, the error
+    /// will be discarded.
+    pub fn step(&mut self) {
+        self.try_step().ok();
+    }
+
+    /// Is the PixelBlinq idle?
+    ///
+    /// This is true when no pixel has a pattern playing or queued.
+    pub fn idle(&self) -> bool {
+        self.pixels.iter().all(RgbBlinq::idle)
+    }
+
+    /// Is the given pixel idle?
+    ///
+    /// Pixels that are not in the chain are always idle.
+    pub fn pixel_idle(&self, pixel: usize) -> bool {
+        match self.pixels.get(pixel) {
+            Some(blinq) => blinq.idle(),
+            None => true,
+        }
+    }
+
+    /// Try to move one step
+    ///
+    /// Every pixel is moved one step, and the frame is written if any
+    /// pixel changed color.
+    pub fn try_step(&mut self) -> Result<(), W::Error> {
+        for blinq in self.pixels.iter_mut() {
+            blinq.step();
+        }
+        self.write_changes()
+    }
+
+    /// Write the frame, if any pixel changed color
+    fn write_changes(&mut self) -> Result<(), W::Error> {
+        let mut changed = false;
+        for (blinq, color) in self.pixels.iter().zip(self.frame.iter_mut()) {
+            let new = blinq.sink().color;
+            changed |= new != *color;
+            *color = new;
+        }
+
+        if changed {
+            self.writer.write_frame(&self.frame)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<W, const P: usize, const N: usize, const L: usize> Step for PixelBlinq<W, P, N, L>
+where
+    W: FrameWrite,
+{
+    type Error = W::Error;
+
+    fn try_step(&mut self) -> Result<(), W::Error> {
+        PixelBlinq::try_step(self)
+    }
+
+    fn idle(&self) -> bool {
+        PixelBlinq::idle(self)
+    }
+
+    fn playing(&self) -> bool {
+        self.pixels.iter().any(Step::playing)
+    }
+
+    fn try_hold(&mut self) -> Result<(), W::Error> {
+        for blinq in self.pixels.iter_mut() {
+            blinq.try_hold().ok();
+        }
+        self.write_changes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pattern;
+
+    struct Recorder {
+        writes: usize,
+        last: [Color; 2],
+    }
+
+    impl FrameWrite for Recorder {
+        type Error = ();
+
+        fn write_frame(&mut self, frame: &[Color]) -> Result<(), ()> {
+            self.writes += 1;
+            self.last.copy_from_slice(frame);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn pixels() {
+        let recorder = Recorder {
+            writes: 0,
+            last: [Color::WHITE; 2],
+        };
+        let mut stepr: PixelBlinq<_, 2, 3, 4> = PixelBlinq::new(recorder);
+        assert_eq!(stepr.frame(), &[Color::OFF; 2]);
+
+        let blink = Pattern::from_u32(0b1100, 4);
+        stepr.enqueue(0, ColorPattern::from_pattern(&blink, Color::RED));
+        stepr.enqueue(1, ColorPattern::solid(Color::BLUE, 3));
+        assert!(stepr.try_enqueue(2, ColorPattern::new()).is_err());
+
+        let expected = [
+            [Color::RED, Color::BLUE],
+            [Color::RED, Color::BLUE],
+            [Color::OFF, Color::BLUE],
+            [Color::OFF, Color::OFF],
+            [Color::OFF, Color::OFF],
+        ];
+        for (i, exp) in expected.iter().enumerate() {
+            stepr.step();
+            assert_eq!(stepr.frame(), exp, "step {}", i);
+        }
+        assert!(stepr.idle());
+        assert!(stepr.pixel_idle(5));
+
+        // Only frames with a change are written
+        let recorder = stepr.release();
+        assert_eq!(recorder.writes, 4);
+        assert_eq!(recorder.last, [Color::OFF; 2]);
+    }
+
+    #[cfg(feature = "smart-leds")]
+    #[test]
+    fn smart_leds() {
+        use smart_leds_trait::{SmartLedsWrite, RGB8};
+
+        struct Leds {
+            last: [RGB8; 2],
+        }
+
+        impl SmartLedsWrite for Leds {
+            type Error = ();
+            type Color = RGB8;
+
+            fn write<T, I>(&mut self, iterator: T) -> Result<(), ()>
+            where
+                T: IntoIterator<Item = I>,
+                I: Into<RGB8>,
+            {
+                for (led, color) in self.last.iter_mut().zip(iterator) {
+                    *led = color.into();
+                }
+                Ok(())
+            }
+        }
+
+        let leds = Leds {
+            last: [RGB8::new(1, 2, 3); 2],
+        };
+        let mut stepr: PixelBlinq<_, 2, 2, 4> = PixelBlinq::new(leds);
+        stepr.enqueue(1, ColorPattern::solid(Color::RED, 1));
+        stepr.step();
+        let leds = stepr.release();
+        assert_eq!(leds.last, [RGB8::new(0, 0, 0), RGB8::new(255, 0, 0)]);
+    }
+}
@@ -0,0 +1,524 @@
+//! Brightness patterns for PWM outputs
+//!
+//! A [`LevelPattern`] holds one 8-bit brightness level per step, and is
+//! played on a PWM output by a [`PwmBlinq`], which is a [`Blinq`] with a
+//! [`Pwm`] output. For pins without a PWM channel, a [`Dimmed`] pattern
+//! plays the levels on a plain `Blinq` instead, by splitting each step
+//! into on/off sub-steps.
+//!
+//! ## Example
+//!
+//! ```rust
+//! # use core::sync::atomic::{AtomicU16, Ordering};
+//! #
+//! # struct FakePwm {
+//! #     duty: &'static AtomicU16,
+//! # }
+//! #
+//! # impl blinq::pwm::PwmOutput for FakePwm {
+//! #     type Error = ();
+//! #     fn max_duty(&self) -> u16 {
+//! #         1000
+//! #     }
+//! #     fn set_duty(&mut self, duty: u16) -> Result<(), ()> {
+//! #         self.duty.store(duty, Ordering::SeqCst);
+//! #         Ok(())
+//! #     }
+//! # }
+//! #
+//! # static DUTY: AtomicU16 = AtomicU16::new(0);
+//! # let pwm = FakePwm { duty: &DUTY };
+//! #
+//! use blinq::pwm::{LevelPattern, PwmBlinq};
+//!
+//! // Room for 4 patterns of up to 32 steps, active-high
+//! let mut blinq: PwmBlinq<FakePwm, 5, 32> = PwmBlinq::new(pwm, false);
+//! blinq.sink_mut().set_gamma(true);
+//!
+//! // Fade up over 16 steps, then back down
+//! let breathe = LevelPattern::ramp(0, 255, 16).append(&LevelPattern::ramp(255, 0, 16));
+//! blinq.enqueue(breathe);
+//!
+//! for _ in 0..32 {
+//!     blinq.step();
+//! }
+//! assert!(blinq.idle());
+//! ```
+
+use crate::{ArrayPattern, Blinq, Output, Pattern, Sequence, Sink};
+
+/// A brightness pattern, with one level per step
+///
+/// A level of `0` is off, and `255` is fully on. Up to `L` steps can be
+/// stored, anything past that is truncated.
+pub type LevelPattern<const L: usize> = ArrayPattern<u8, L>;
+
+impl<const L: usize> ArrayPattern<u8, L> {
+    /// Create a pattern that uses all `L` levels
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use blinq::pwm::LevelPattern;
+    ///
+    /// // Half brightness, then full brightness
+    /// let pat = LevelPattern::from_levels([128, 128, 255, 255]);
+    /// assert_eq!(pat.steps(), 4);
+    /// ```
+    pub const fn from_levels(levels: [u8; L]) -> Self {
+        Self::from_values(levels)
+    }
+
+    /// Create a linear fade from `from` to `to` over `steps` steps
+    ///
+    /// Both `from` and `to` are included. If `steps` is larger than `L`,
+    /// the fade will be truncated.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use blinq::pwm::LevelPattern;
+    ///
+    /// let fade: LevelPattern<8> = LevelPattern::ramp(0, 255, 4);
+    /// assert_eq!(fade.steps(), 4);
+    /// assert_eq!(fade.get(0), 0);
+    /// assert_eq!(fade.get(1), 85);
+    /// assert_eq!(fade.get(3), 255);
+    /// ```
+    pub const fn ramp(from: u8, to: u8, steps: usize) -> Self {
+        let mut out = Self::new();
+        let span = to as i32 - from as i32;
+        let mut i = 0;
+        while i < steps && i < L {
+            let level = if steps == 1 {
+                from
+            } else {
+                (from as i32 + span * i as i32 / (steps as i32 - 1)) as u8
+            };
+            out = out.push(level);
+            i += 1;
+        }
+        out
+    }
+}
+
+/// On/off patterns become full brightness or off
+impl<const L: usize> From<Pattern> for ArrayPattern<u8, L> {
+    fn from(pat: Pattern) -> Self {
+        let mut out = Self::new();
+        for step in 0..pat.steps() {
+            out = out.push(if pat.get(step) { 255 } else { 0 });
+        }
+        out
+    }
+}
+
+/// A brightness pattern, modulated for a plain on/off output
+///
+/// Each level of the pattern is played as `S` sub-steps, of which a
+/// share proportional to the level is on. The on sub-steps are spread
+/// out as evenly as possible (delta-sigma modulation), rather than
+/// grouped together, to reduce flicker. The [`Blinq`] should be stepped
+/// `S` times as fast as the pattern would otherwise be played, and fast
+/// enough that each step of the pattern is not seen to flicker.
+///
+/// ## Example
+///
+/// ```rust
+/// # let led = core::cell::Cell::new(false);
+/// #
+/// use blinq::{pwm::{Dimmed, LevelPattern}, Blinq};
+///
+/// // Each level is played as 16 sub-steps
+/// let mut blinq: Blinq<_, 4, Dimmed<8, 16>> = Blinq::with_sink(|on: bool| led.set(on));
+/// blinq.enqueue(Dimmed::with_gamma(LevelPattern::ramp(0, 255, 8)));
+///
+/// for _ in 0..(8 * 16) {
+///     blinq.step();
+/// }
+/// assert!(blinq.idle());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dimmed<const L: usize, const S: usize> {
+    pattern: LevelPattern<L>,
+    gamma: bool,
+}
+
+impl<const L: usize, const S: usize> Dimmed<L, S> {
+    /// Modulate the levels of a pattern as they are
+    pub const fn new(pattern: LevelPattern<L>) -> Self {
+        Dimmed {
+            pattern,
+            gamma: false,
+        }
+    }
+
+    /// Modulate the levels of a pattern, after gamma correction
+    ///
+    /// See [`gamma`] for details.
+    pub const fn with_gamma(pattern: LevelPattern<L>) -> Self {
+        Dimmed {
+            pattern,
+            gamma: true,
+        }
+    }
+
+    /// The number of on sub-steps for the given level
+    fn on_steps(&self, level: u8) -> u64 {
+        if self.gamma {
+            (u64::from(gamma(level)) * S as u64 + 0x7FFF) / 0xFFFF
+        } else {
+            (u64::from(level) * S as u64 + 127) / 255
+        }
+    }
+}
+
+impl<const L: usize, const S: usize> From<LevelPattern<L>> for Dimmed<L, S> {
+    fn from(pattern: LevelPattern<L>) -> Self {
+        Self::new(pattern)
+    }
+}
+
+impl<const L: usize, const S: usize> Sequence for Dimmed<L, S> {
+    type Value = bool;
+
+    fn steps(&self) -> usize {
+        self.pattern.steps() * S
+    }
+
+    fn get(&self, step: usize) -> bool {
+        let on = self.on_steps(self.pattern.get(step / S));
+        let sub = (step % S) as u64;
+        let size = S as u64;
+        (sub + 1) * on / size > sub * on / size
+    }
+}
+
+/// Correct a brightness level for perceived brightness
+///
+/// This applies a gamma of 2.2, and scales the result to the full
+/// range of a `u16`.
+pub const fn gamma(level: u8) -> u16 {
+    GAMMA[level as usize]
+}
+
+const GAMMA: [u16; 256] = [
+    0, 0, 2, 4, 7, 11, 17, 24, 32, 42, 53, 65, 79, 94, 111, 129, 148, 169, 192, 216, 242, 270, 299,
+    330, 362, 396, 432, 469, 508, 549, 591, 635, 681, 729, 779, 830, 883, 938, 995, 1053, 1113,
+    1175, 1239, 1305, 1373, 1443, 1514, 1587, 1663, 1740, 1819, 1900, 1983, 2068, 2155, 2243, 2334,
+    2427, 2521, 2618, 2717, 2817, 2920, 3024, 3131, 3240, 3350, 3463, 3578, 3694, 3813, 3934, 4057,
+    4182, 4309, 4438, 4570, 4703, 4838, 4976, 5115, 5257, 5401, 5547, 5695, 5845, 5998, 6152, 6309,
+    6468, 6629, 6792, 6957, 7124, 7294, 7466, 7640, 7816, 7994, 8175, 8358, 8543, 8730, 8919, 9111,
+    9305, 9501, 9699, 9900, 10102, 10307, 10515, 10724, 10936, 11150, 11366, 11585, 11806, 12029,
+    12254, 12482, 12712, 12944, 13179, 13416, 13655, 13896, 14140, 14386, 14635, 14885, 15138,
+    15394, 15652, 15912, 16174, 16439, 16706, 16975, 17247, 17521, 17798, 18077, 18358, 18642,
+    18928, 19216, 19507, 19800, 20095, 20393, 20694, 20996, 21301, 21609, 21919, 22231, 22546,
+    22863, 23182, 23504, 23829, 24156, 24485, 24817, 25151, 25487, 25826, 26168, 26512, 26858,
+    27207, 27558, 27912, 28268, 28627, 28988, 29351, 29717, 30086, 30457, 30830, 31206, 31585,
+    31966, 32349, 32735, 33124, 33514, 33908, 34304, 34702, 35103, 35507, 35913, 36321, 36732,
+    37146, 37562, 37981, 38402, 38825, 39252, 39680, 40112, 40546, 40982, 41421, 41862, 42306,
+    42753, 43202, 43654, 44108, 44565, 45025, 45487, 45951, 46418, 46888, 47360, 47835, 48313,
+    48793, 49275, 49761, 50249, 50739, 51232, 51728, 52226, 52727, 53230, 53736, 54245, 54756,
+    55270, 55787, 56306, 56828, 57352, 57879, 58409, 58941, 59476, 60014, 60554, 61097, 61642,
+    62190, 62741, 63295, 63851, 64410, 64971, 65535,
+];
+
+/// A PWM output with a `u16` duty cycle
+///
+/// This is implemented for the `PwmPin` trait of embedded-hal 0.2, with
+/// a `Duty` of `u16`, when the `eh0` feature is enabled. Channels with the
+/// `pwm::SetDutyCycle` trait of embedded-hal 1.0 can be wrapped in a
+/// `PwmPin1`. The output should already be enabled.
+pub trait PwmOutput {
+    /// The error returned when setting the duty cycle fails
+    type Error;
+
+    /// The duty cycle that is fully on
+    fn max_duty(&self) -> u16;
+
+    /// Set the duty cycle, from `0` to `self.max_duty()`
+    fn set_duty(&mut self, duty: u16) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "eh0")]
+impl<T> PwmOutput for T
+where
+    T: embedded_hal::PwmPin<Duty = u16>,
+{
+    type Error = core::convert::Infallible;
+
+    fn max_duty(&self) -> u16 {
+        self.get_max_duty()
+    }
+
+    fn set_duty(&mut self, duty: u16) -> Result<(), Self::Error> {
+        embedded_hal::PwmPin::set_duty(self, duty);
+        Ok(())
+    }
+}
+
+/// An embedded-hal 1.0 PWM channel, used as a [`PwmOutput`]
+///
+/// This requires the `eh1` feature.
+#[cfg(feature = "eh1")]
+pub struct PwmPin1<T> {
+    pwm: T,
+}
+
+#[cfg(feature = "eh1")]
+impl<T> PwmPin1<T>
+where
+    T: embedded_hal_1::pwm::SetDutyCycle,
+{
+    /// Wrap a PWM channel
+    pub fn new(pwm: T) -> Self {
+        Self { pwm }
+    }
+
+    /// Release the PWM channel
+    pub fn release(self) -> T {
+        self.pwm
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<T> PwmOutput for PwmPin1<T>
+where
+    T: embedded_hal_1::pwm::SetDutyCycle,
+{
+    type Error = T::Error;
+
+    fn max_duty(&self) -> u16 {
+        self.pwm.max_duty_cycle()
+    }
+
+    fn set_duty(&mut self, duty: u16) -> Result<(), Self::Error> {
+        self.pwm.set_duty_cycle(duty)
+    }
+}
+
+/// A PWM output, used as a [`Sink`] for brightness levels
+pub struct Pwm<G> {
+    pwm: G,
+    active_low: bool,
+    gamma: bool,
+}
+
+impl<G> Pwm<G>
+where
+    G: PwmOutput,
+{
+    /// Wrap a PWM output
+    ///
+    /// If `active_low` is true, the duty cycle is inverted. Gamma
+    /// correction is disabled by default.
+    pub fn new(pwm: G, active_low: bool) -> Self {
+        Self {
+            pwm,
+            active_low,
+            gamma: false,
+        }
+    }
+
+    /// Enable or disable gamma correction of levels
+    ///
+    /// See [`gamma`] for details.
+    pub fn set_gamma(&mut self, enabled: bool) {
+        self.gamma = enabled;
+    }
+
+    /// Release the PWM output
+    pub fn release(self) -> G {
+        self.pwm
+    }
+}
+
+impl<G> Sink<u8> for Pwm<G>
+where
+    G: PwmOutput,
+{
+    type Error = G::Error;
+
+    fn set(&mut self, level: u8) -> Result<(), G::Error> {
+        let duty = duty(level, self.pwm.max_duty(), self.gamma, self.active_low);
+        self.pwm.set_duty(duty)
+    }
+}
+
+impl<G> Output for Pwm<G>
+where
+    G: PwmOutput,
+{
+    type Driver = G;
+
+    fn wrap(pwm: G, active_low: bool) -> Self {
+        Pwm::new(pwm, active_low)
+    }
+
+    fn release(self) -> G {
+        Pwm::release(self)
+    }
+}
+
+/// The duty cycle for a level, out of `max`
+pub(crate) fn duty(level: u8, max: u16, gamma_correct: bool, active_low: bool) -> u16 {
+    let frac = if gamma_correct {
+        u32::from(gamma(level))
+    } else {
+        u32::from(level) * 257
+    };
+    let max = u32::from(max);
+    let duty = (frac * max + 0x7FFF) / 0xFFFF;
+    if active_low {
+        (max - duty) as u16
+    } else {
+        duty as u16
+    }
+}
+
+/// A queue of brightness patterns, played on a PWM output
+///
+/// This is a [`Blinq`] with a [`Pwm`] output, holding up to `N - 1`
+/// patterns of up to `L` steps. It can be created straight from a
+/// [`PwmOutput`] with [`Blinq::new`].
+pub type PwmBlinq<G, const N: usize, const L: usize> = Blinq<Pwm<G>, N, LevelPattern<L>>;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use core::sync::atomic::{AtomicU16, Ordering};
+
+    pub(crate) struct FakePwm {
+        pub(crate) duty: &'static AtomicU16,
+    }
+
+    impl PwmOutput for FakePwm {
+        type Error = ();
+        fn max_duty(&self) -> u16 {
+            1000
+        }
+        fn set_duty(&mut self, duty: u16) -> Result<(), ()> {
+            self.duty.store(duty, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "eh0")]
+    #[test]
+    fn eh0() {
+        struct Eh0(u16);
+
+        impl embedded_hal::PwmPin for Eh0 {
+            type Duty = u16;
+            fn disable(&mut self) {}
+            fn enable(&mut self) {}
+            fn get_duty(&self) -> u16 {
+                self.0
+            }
+            fn get_max_duty(&self) -> u16 {
+                1000
+            }
+            fn set_duty(&mut self, duty: u16) {
+                self.0 = duty;
+            }
+        }
+
+        let mut pwm = Pwm::new(Eh0(0), false);
+        pwm.set(51).unwrap();
+        assert_eq!(pwm.release().0, 200);
+    }
+
+    #[cfg(feature = "eh1")]
+    #[test]
+    fn eh1() {
+        struct Eh1(u16);
+
+        impl embedded_hal_1::pwm::ErrorType for Eh1 {
+            type Error = core::convert::Infallible;
+        }
+
+        impl embedded_hal_1::pwm::SetDutyCycle for Eh1 {
+            fn max_duty_cycle(&self) -> u16 {
+                1000
+            }
+            fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+                self.0 = duty;
+                Ok(())
+            }
+        }
+
+        let mut pwm = Pwm::new(PwmPin1::new(Eh1(0)), false);
+        pwm.set(51).unwrap();
+        assert_eq!(pwm.release().release().0, 200);
+    }
+
+    #[test]
+    fn levels() {
+        let ramp = LevelPattern::<4>::ramp(10, 40, 4);
+        assert_eq!(ramp, LevelPattern::from_levels([10, 20, 30, 40]));
+        let down = LevelPattern::<8>::ramp(255, 0, 3);
+        assert_eq!((down.get(0), down.get(1), down.get(2)), (255, 128, 0));
+
+        // Appending truncates at L
+        let both = ramp.append(&LevelPattern::from_levels([1, 2, 3, 4]));
+        assert_eq!(both, ramp);
+
+        let pat: LevelPattern<4> = Pattern::from_u32(0b101, 3).into();
+        assert_eq!(pat.steps(), 3);
+        assert_eq!((pat.get(0), pat.get(1), pat.get(2)), (255, 0, 255));
+
+        assert_eq!(gamma(0), 0);
+        assert_eq!(gamma(255), 0xFFFF);
+    }
+
+    #[test]
+    fn pwm() {
+        static DUTY: AtomicU16 = AtomicU16::new(123);
+        let pwm = FakePwm { duty: &DUTY };
+        let mut stepr: PwmBlinq<FakePwm, 4, 4> = PwmBlinq::new(pwm, false);
+        assert_eq!(DUTY.load(Ordering::SeqCst), 0);
+
+        stepr.enqueue(LevelPattern::from_levels([0, 51, 255, 128]));
+        stepr.enqueue(LevelPattern::new());
+        stepr.enqueue(Pattern::from_u32(0b1, 1));
+        for exp in [0, 200, 1000, 502, 1000, 0] {
+            stepr.step();
+            assert_eq!(DUTY.load(Ordering::SeqCst), exp);
+        }
+        assert!(stepr.idle());
+
+        // Gamma correction and active low
+        let mut stepr: PwmBlinq<FakePwm, 3, 4> = PwmBlinq::new(stepr.release(), true);
+        assert_eq!(DUTY.load(Ordering::SeqCst), 1000);
+        stepr.sink_mut().set_gamma(true);
+        stepr.enqueue(LevelPattern::ramp(128, 255, 2));
+        stepr.step();
+        assert_eq!(DUTY.load(Ordering::SeqCst), 1000 - 220);
+        stepr.step();
+        assert_eq!(DUTY.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn dimmed() {
+        use crate::tests::{FakeGpio, Gpio};
+        use crate::Blinq;
+        use core::sync::atomic::AtomicBool;
+
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut stepr: Blinq<Gpio<FakeGpio>, 3, Dimmed<4, 4>> = Blinq::new(fg, false);
+        stepr.enqueue(LevelPattern::from_levels([128, 255, 64, 0]));
+
+        let expected = [0, 1, 0, 1, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0];
+        for (i, exp) in expected.iter().enumerate() {
+            stepr.step();
+            assert_eq!(STATE.load(Ordering::SeqCst), *exp == 1, "step {}", i);
+        }
+        assert!(stepr.idle());
+
+        // Gamma correction dims the middle levels
+        let pat: Dimmed<1, 8> = Dimmed::with_gamma(LevelPattern::from_levels([128]));
+        assert_eq!((0..8).filter(|i| pat.get(*i)).count(), 2);
+    }
+}
@@ -0,0 +1,313 @@
+//! Color patterns for RGB LEDs
+//!
+//! A [`ColorPattern`] holds one [`Color`] per step, and is played by an
+//! [`RgbBlinq`], which is a [`Blinq`] with an RGB output. All three
+//! channels are updated together on each step, using either three on/off
+//! outputs like GPIOs with [`RgbPins`], or three PWM outputs with
+//! [`RgbPwm`].
+//!
+//! ## Example
+//!
+//! ```rust
+//! # let (red, green, blue) = (
+//! #     core::cell::Cell::new(false),
+//! #     core::cell::Cell::new(false),
+//! #     core::cell::Cell::new(false),
+//! # );
+//! #
+//! use blinq::{patterns, rgb::{Color, ColorPattern, RgbBlinq, RgbPins}};
+//!
+//! // An RGB LED on three outputs. GPIOs would be wrapped in a `Gpio` or
+//! // `Gpio1`, which are active-low for a common-anode LED.
+//! let pins = RgbPins::new(
+//!     |on: bool| red.set(on),
+//!     |on: bool| green.set(on),
+//!     |on: bool| blue.set(on),
+//! );
+//! let mut blinq: RgbBlinq<_, 4, 32> = RgbBlinq::with_sink(pins);
+//!
+//! // SOS in red, then one step of cyan
+//! blinq.enqueue(ColorPattern::from_pattern(&patterns::morse::SOS, Color::RED));
+//! blinq.enqueue(ColorPattern::solid(Color::CYAN, 1));
+//!
+//! for _ in 0..25 {
+//!     blinq.step();
+//! }
+//! assert!(blinq.idle());
+//! ```
+
+use crate::pwm::{duty, PwmOutput};
+use crate::{ArrayPattern, Blinq, Pattern, Sink, StepValue};
+
+/// A color, with one 8-bit level per channel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const OFF: Color = Color::new(0, 0, 0);
+    pub const WHITE: Color = Color::new(255, 255, 255);
+    pub const RED: Color = Color::new(255, 0, 0);
+    pub const GREEN: Color = Color::new(0, 255, 0);
+    pub const BLUE: Color = Color::new(0, 0, 255);
+    pub const YELLOW: Color = Color::new(255, 255, 0);
+    pub const CYAN: Color = Color::new(0, 255, 255);
+    pub const MAGENTA: Color = Color::new(255, 0, 255);
+    pub const ORANGE: Color = Color::new(255, 64, 0);
+    pub const PURPLE: Color = Color::new(128, 0, 255);
+
+    /// Create a new color from its channel levels
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+}
+
+impl StepValue for Color {
+    const OFF: Self = Color::OFF;
+}
+
+/// A color pattern, with one color per step
+///
+/// Up to `L` steps can be stored, anything past that is truncated.
+pub type ColorPattern<const L: usize> = ArrayPattern<Color, L>;
+
+impl<const L: usize> ArrayPattern<Color, L> {
+    /// Create a pattern that uses all `L` colors
+    pub const fn from_colors(colors: [Color; L]) -> Self {
+        Self::from_values(colors)
+    }
+
+    /// Create a pattern from an on/off pattern, where "on" steps show `color`
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use blinq::{Pattern, rgb::{Color, ColorPattern}};
+    ///
+    /// let blink: ColorPattern<4> = ColorPattern::from_pattern(
+    ///     &Pattern::from_u32(0b1100, 4),
+    ///     Color::GREEN,
+    /// );
+    /// assert_eq!(blink.get(0), Color::GREEN);
+    /// assert_eq!(blink.get(2), Color::OFF);
+    /// ```
+    pub const fn from_pattern(pat: &Pattern, color: Color) -> Self {
+        let mut out = Self::new();
+        // A truncating `Pattern::append` may give a `used` larger than 32
+        let used = if pat.used > 32 { 32 } else { pat.used as usize };
+        let mut i = 0;
+        while i < used {
+            let on = (pat.pattern >> i) & 1 == 1;
+            out = out.push(if on { color } else { Color::OFF });
+            i += 1;
+        }
+        out
+    }
+}
+
+/// An RGB LED on three on/off outputs
+///
+/// Each output is a `Sink<bool>`, like a GPIO wrapped in a `Gpio`. For
+/// common-anode LEDs, the outputs should be active-low. Each channel is
+/// on when its level is at least 128.
+pub struct RgbPins<R, G, B> {
+    red: R,
+    green: G,
+    blue: B,
+}
+
+impl<R, G, B> RgbPins<R, G, B>
+where
+    R: Sink<bool>,
+    G: Sink<bool, Error = R::Error>,
+    B: Sink<bool, Error = R::Error>,
+{
+    /// Create a new set of RGB outputs
+    pub fn new(red: R, green: G, blue: B) -> Self {
+        Self { red, green, blue }
+    }
+
+    /// Release the outputs
+    pub fn release(self) -> (R, G, B) {
+        (self.red, self.green, self.blue)
+    }
+}
+
+impl<R, G, B> Sink<Color> for RgbPins<R, G, B>
+where
+    R: Sink<bool>,
+    G: Sink<bool, Error = R::Error>,
+    B: Sink<bool, Error = R::Error>,
+{
+    type Error = R::Error;
+
+    fn set(&mut self, color: Color) -> Result<(), R::Error> {
+        let r = self.red.set(color.r >= 128);
+        let g = self.green.set(color.g >= 128);
+        let b = self.blue.set(color.b >= 128);
+        r.and(g).and(b)
+    }
+}
+
+/// An RGB LED on three PWM outputs
+pub struct RgbPwm<R, G, B> {
+    red: R,
+    green: G,
+    blue: B,
+    active_low: bool,
+    gamma: bool,
+}
+
+impl<R, G, B> RgbPwm<R, G, B>
+where
+    R: PwmOutput,
+    G: PwmOutput<Error = R::Error>,
+    B: PwmOutput<Error = R::Error>,
+{
+    /// Create a new set of RGB PWM outputs
+    ///
+    /// If `active_low` is true, the duty cycles are inverted. Gamma
+    /// correction is disabled by default.
+    pub fn new(red: R, green: G, blue: B, active_low: bool) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            active_low,
+            gamma: false,
+        }
+    }
+
+    /// Enable or disable gamma correction of each channel
+    ///
+    /// See [`gamma`](crate::pwm::gamma) for details.
+    pub fn set_gamma(&mut self, enabled: bool) {
+        self.gamma = enabled;
+    }
+
+    /// Release the PWM outputs
+    pub fn release(self) -> (R, G, B) {
+        (self.red, self.green, self.blue)
+    }
+}
+
+impl<R, G, B> Sink<Color> for RgbPwm<R, G, B>
+where
+    R: PwmOutput,
+    G: PwmOutput<Error = R::Error>,
+    B: PwmOutput<Error = R::Error>,
+{
+    type Error = R::Error;
+
+    fn set(&mut self, color: Color) -> Result<(), R::Error> {
+        let (gamma, active_low) = (self.gamma, self.active_low);
+        let r = duty(color.r, self.red.max_duty(), gamma, active_low);
+        let g = duty(color.g, self.green.max_duty(), gamma, active_low);
+        let b = duty(color.b, self.blue.max_duty(), gamma, active_low);
+        let r = self.red.set_duty(r);
+        let g = self.green.set_duty(g);
+        let b = self.blue.set_duty(b);
+        r.and(g).and(b)
+    }
+}
+
+/// A queue of color patterns, played on an RGB LED
+///
+/// This is a [`Blinq`] with an RGB output, like [`RgbPins`] or [`RgbPwm`],
+/// holding up to `N - 1` patterns of up to `L` steps.
+pub type RgbBlinq<O, const N: usize, const L: usize> = Blinq<O, N, ColorPattern<L>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pwm::tests::FakePwm;
+    use crate::tests::{FakeGpio, Gpio};
+
+    use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+
+    #[test]
+    fn pins() {
+        static RED: AtomicBool = AtomicBool::new(true);
+        static GREEN: AtomicBool = AtomicBool::new(true);
+        static BLUE: AtomicBool = AtomicBool::new(true);
+        let pins = RgbPins::new(
+            Gpio::new(FakeGpio { state: &RED }, false),
+            Gpio::new(FakeGpio { state: &GREEN }, false),
+            Gpio::new(FakeGpio { state: &BLUE }, false),
+        );
+        let mut stepr: RgbBlinq<_, 3, 4> = RgbBlinq::with_sink(pins);
+        let rgb = || {
+            (
+                RED.load(Ordering::SeqCst),
+                GREEN.load(Ordering::SeqCst),
+                BLUE.load(Ordering::SeqCst),
+            )
+        };
+        assert_eq!(rgb(), (false, false, false));
+
+        stepr.enqueue(ColorPattern::from_colors([
+            Color::RED,
+            Color::CYAN,
+            Color::PURPLE,
+            Color::ORANGE,
+        ]));
+        stepr.enqueue(ColorPattern::from_pattern(
+            &Pattern::from_u32(0b01, 2),
+            Color::WHITE,
+        ));
+
+        let expected = [
+            (true, false, false),
+            (false, true, true),
+            (true, false, true),
+            (true, false, false),
+            (false, false, false),
+            (true, true, true),
+            (false, false, false),
+        ];
+        for (i, exp) in expected.iter().enumerate() {
+            stepr.step();
+            assert_eq!(rgb(), *exp, "step {}", i);
+        }
+        assert!(stepr.idle());
+
+        // Steps truncated by appending are dropped
+        let long = Pattern::from_u32(0, 30).append(&Pattern::from_u32(0b1111, 4));
+        let pat = ColorPattern::<64>::from_pattern(&long, Color::WHITE);
+        assert_eq!(pat.steps(), 32);
+        assert_eq!((pat.get(29), pat.get(30)), (Color::OFF, Color::WHITE));
+    }
+
+    #[test]
+    fn pwm() {
+        static RED: AtomicU16 = AtomicU16::new(0);
+        static GREEN: AtomicU16 = AtomicU16::new(0);
+        static BLUE: AtomicU16 = AtomicU16::new(0);
+        let pwm = RgbPwm::new(
+            FakePwm { duty: &RED },
+            FakePwm { duty: &GREEN },
+            FakePwm { duty: &BLUE },
+            true,
+        );
+        let mut stepr: RgbBlinq<_, 3, 4> = RgbBlinq::with_sink(pwm);
+        let rgb = || {
+            (
+                RED.load(Ordering::SeqCst),
+                GREEN.load(Ordering::SeqCst),
+                BLUE.load(Ordering::SeqCst),
+            )
+        };
+        assert_eq!(rgb(), (1000, 1000, 1000));
+
+        stepr.enqueue(ColorPattern::solid(Color::PURPLE, 2));
+        stepr.step();
+        assert_eq!(rgb(), (498, 1000, 0));
+        stepr.step();
+        stepr.step();
+        assert_eq!(rgb(), (1000, 1000, 1000));
+        assert!(stepr.idle());
+    }
+}
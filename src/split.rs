@@ -0,0 +1,411 @@
+//! Producer and consumer halves of a split Blinq
+
+use heapless::spsc::{Consumer, Producer};
+
+#[cfg(feature = "async")]
+use crate::Signal;
+use crate::{Core, Entry, Events, Pattern, Repeat, Sequence, Sink, Step, Tag};
+
+/// The producer half of a split [`Blinq`](crate::Blinq)
+///
+/// This can enqueue patterns from any context, without locking. Created
+/// by [`Blinq::split`](crate::Blinq::split).
+pub struct BlinqProducer<'a, const N: usize, P = Pattern> {
+    producer: Producer<'a, Entry<P>, N>,
+    dropped: &'a mut usize,
+    #[cfg(feature = "async")]
+    signal: &'a Signal,
+}
+
+impl<'a, const N: usize, P> BlinqProducer<'a, N, P>
+where
+    P: Sequence,
+{
+    pub(crate) fn new(
+        producer: Producer<'a, Entry<P>, N>,
+        dropped: &'a mut usize,
+        #[cfg(feature = "async")] signal: &'a Signal,
+    ) -> Self {
+        Self {
+            producer,
+            dropped,
+            #[cfg(feature = "async")]
+            signal,
+        }
+    }
+
+    /// Enqueue a new pattern into the queue
+    ///
+    /// If the queue is currently full, the pattern will be discarded
+    pub fn enqueue(&mut self, pat: impl Into<P>) {
+        if self.try_enqueue(pat).is_err() {
+            *self.dropped = self.dropped.wrapping_add(1);
+        }
+    }
+
+    /// Try to enqueue a new pattern into the queue
+    ///
+    /// If the queue is currently full, an error will be returned
+    pub fn try_enqueue(&mut self, pat: impl Into<P>) -> Result<(), P> {
+        self.try_enqueue_entry(pat.into(), Repeat::Times(1), None)
+    }
+
+    /// Enqueue a new pattern, which will be played `n` times in a row
+    ///
+    /// If the queue is currently full, the pattern will be discarded
+    pub fn enqueue_repeat(&mut self, pat: impl Into<P>, n: usize) {
+        if self.try_enqueue_repeat(pat, n).is_err() {
+            *self.dropped = self.dropped.wrapping_add(1);
+        }
+    }
+
+    /// Try to enqueue a new pattern, which will be played `n` times in a row
+    ///
+    /// If the queue is currently full, an error will be returned
+    pub fn try_enqueue_repeat(&mut self, pat: impl Into<P>, n: usize) -> Result<(), P> {
+        self.try_enqueue_entry(pat.into(), Repeat::Times(n), None)
+    }
+
+    /// Enqueue a new pattern, which will be played in a loop
+    ///
+    /// See [`Blinq::enqueue_loop`](crate::Blinq::enqueue_loop) for details.
+    /// If the queue is currently full, the pattern will be discarded.
+    pub fn enqueue_loop(&mut self, pat: impl Into<P>) {
+        if self.try_enqueue_loop(pat).is_err() {
+            *self.dropped = self.dropped.wrapping_add(1);
+        }
+    }
+
+    /// Try to enqueue a new pattern, which will be played in a loop
+    ///
+    /// See [`Blinq::enqueue_loop`](crate::Blinq::enqueue_loop) for details.
+    /// If the queue is currently full, an error will be returned.
+    pub fn try_enqueue_loop(&mut self, pat: impl Into<P>) -> Result<(), P> {
+        self.try_enqueue_entry(pat.into(), Repeat::Forever, None)
+    }
+
+    /// Enqueue a new pattern with a tag, played `repeat` times
+    ///
+    /// See [`Blinq::enqueue_tagged`](crate::Blinq::enqueue_tagged) for
+    /// details. If the queue is currently full, the pattern will be discarded.
+    pub fn enqueue_tagged(&mut self, pat: impl Into<P>, repeat: Repeat, tag: Tag) {
+        if self.try_enqueue_tagged(pat, repeat, tag).is_err() {
+            *self.dropped = self.dropped.wrapping_add(1);
+        }
+    }
+
+    /// Try to enqueue a new pattern with a tag, played `repeat` times
+    ///
+    /// See [`Blinq::enqueue_tagged`](crate::Blinq::enqueue_tagged) for
+    /// details. If the queue is currently full, an error will be returned.
+    pub fn try_enqueue_tagged(
+        &mut self,
+        pat: impl Into<P>,
+        repeat: Repeat,
+        tag: Tag,
+    ) -> Result<(), P> {
+        self.try_enqueue_entry(pat.into(), repeat, Some(tag))
+    }
+
+    /// The number of patterns waiting in the queue
+    pub fn len(&self) -> usize {
+        self.producer.len()
+    }
+
+    /// Are there no patterns waiting in the queue?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of patterns that can be enqueued before the queue is full
+    pub fn remaining(&self) -> usize {
+        self.producer.capacity() - self.producer.len()
+    }
+
+    fn try_enqueue_entry(&mut self, pat: P, repeat: Repeat, tag: Option<Tag>) -> Result<(), P> {
+        self.producer
+            .enqueue(Entry { pat, repeat, tag })
+            .map_err(|entry| entry.pat)?;
+
+        #[cfg(feature = "async")]
+        self.signal.signal(());
+
+        Ok(())
+    }
+}
+
+/// The consumer half of a split [`Blinq`](crate::Blinq)
+///
+/// This steps the queue and drives the GPIO. Created by
+/// [`Blinq::split`](crate::Blinq::split).
+pub struct BlinqConsumer<'a, S, const N: usize, P = Pattern>
+where
+    P: Sequence,
+{
+    consumer: Consumer<'a, Entry<P>, N>,
+    core: &'a mut Core<S, P>,
+    #[cfg(feature = "async")]
+    signal: &'a Signal,
+}
+
+impl<'a, S, const N: usize, P> BlinqConsumer<'a, S, N, P>
+where
+    S: Sink<P::Value>,
+    P: Sequence,
+{
+    pub(crate) fn new(
+        consumer: Consumer<'a, Entry<P>, N>,
+        core: &'a mut Core<S, P>,
+        #[cfg(feature = "async")] signal: &'a Signal,
+    ) -> Self {
+        Self {
+            consumer,
+            core,
+            #[cfg(feature = "async")]
+            signal,
+        }
+    }
+
+    /// Play the queue forever, waiting `period_us` microseconds between steps
+    ///
+    /// When there is nothing left to play, and no idle pattern is set, the
+    /// GPIO is driven to the inactive state and this sleeps until the
+    /// [`BlinqProducer`] enqueues a new pattern.
+    ///
+    /// This requires the `async` feature.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,ignore
+    /// #[embassy_executor::task]
+    /// async fn blink(mut consumer: BlinqConsumer<'static, Gpio<Led>, 8>) {
+    ///     consumer.run(&mut embassy_time::Delay, 250_000).await
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn run<D>(&mut self, delay: &mut D, period_us: u32) -> !
+    where
+        D: embedded_hal_async::delay::DelayNs,
+    {
+        loop {
+            if self.core.quiescent(&self.consumer) {
+                // Drive the GPIO inactive, then sleep until something is enqueued
+                self.step();
+                self.signal.wait().await;
+                continue;
+            }
+
+            self.step();
+            delay.delay_us(period_us).await;
+        }
+    }
+
+    /// Move the queue one step
+    ///
+    /// See [`Blinq::step`](crate::Blinq::step) for details.
+    pub fn step(&mut self) -> Events {
+        let _ = self.try_step();
+        self.events()
+    }
+
+    /// Try to move the queue one step
+    ///
+    /// See [`Blinq::try_step`](crate::Blinq::try_step) for details.
+    pub fn try_step(&mut self) -> Result<Events, S::Error> {
+        self.core.try_step(&mut self.consumer)
+    }
+
+    /// Is the queue empty?
+    pub fn idle(&self) -> bool {
+        self.core.idle(&self.consumer)
+    }
+
+    /// Move the queue `n` steps at once
+    ///
+    /// See [`Blinq::advance`](crate::Blinq::advance) for details.
+    pub fn advance(&mut self, n: usize) -> Events {
+        let _ = self.try_advance(n);
+        self.events()
+    }
+
+    /// Try to move the queue `n` steps at once
+    ///
+    /// See [`Blinq::advance`](crate::Blinq::advance) for details.
+    pub fn try_advance(&mut self, n: usize) -> Result<Events, S::Error> {
+        self.core.try_advance(n, &mut self.consumer)
+    }
+
+    /// The events of the last step
+    ///
+    /// See [`Blinq::events`](crate::Blinq::events) for details.
+    pub fn events(&self) -> Events {
+        self.core.events
+    }
+
+    /// The number of steps until the GPIO next changes state
+    ///
+    /// See [`Blinq::next_edge`](crate::Blinq::next_edge) for details. Only
+    /// the first pattern waiting in the queue can be seen by the consumer,
+    /// so if there are more, this may return the number of steps until that
+    /// pattern has been played, rather than until the next change.
+    pub fn next_edge(&self) -> Option<usize> {
+        self.core.next_edge(&self.consumer)
+    }
+
+    /// Stop the currently playing loop
+    ///
+    /// See [`Blinq::cancel_loop`](crate::Blinq::cancel_loop) for details.
+    pub fn cancel_loop(&mut self) {
+        self.core.cancel_loop();
+    }
+
+    /// Play a pattern immediately, interrupting the current pattern
+    ///
+    /// See [`Blinq::interrupt`](crate::Blinq::interrupt) for details.
+    pub fn interrupt(&mut self, pat: impl Into<P>) {
+        self.core.interrupt(pat.into());
+    }
+
+    /// Remove all patterns waiting in the queue
+    pub fn clear(&mut self) {
+        while self.consumer.dequeue().is_some() {}
+    }
+
+    /// Abort the currently playing pattern
+    ///
+    /// See [`Blinq::skip`](crate::Blinq::skip) for details.
+    pub fn skip(&mut self) {
+        self.core.skip();
+    }
+
+    /// The next pattern waiting in the queue, if any
+    pub fn peek(&self) -> Option<&P> {
+        self.consumer.peek().map(|entry| &entry.pat)
+    }
+
+    /// The number of patterns waiting in the queue
+    pub fn len(&self) -> usize {
+        self.consumer.len()
+    }
+
+    /// Are there no patterns waiting in the queue?
+    pub fn is_empty(&self) -> bool {
+        !self.consumer.ready()
+    }
+
+    /// Set a pattern to be played whenever the queue is empty
+    ///
+    /// See [`Blinq::set_idle_pattern`](crate::Blinq::set_idle_pattern) for
+    /// details.
+    pub fn set_idle_pattern(&mut self, pat: impl Into<P>) {
+        self.core.set_idle_pattern(Some(pat.into()));
+    }
+
+    /// Remove the idle pattern, if any
+    pub fn clear_idle_pattern(&mut self) {
+        self.core.set_idle_pattern(None);
+    }
+}
+
+impl<S, const N: usize, P> Step for BlinqConsumer<'_, S, N, P>
+where
+    S: Sink<P::Value>,
+    P: Sequence,
+{
+    type Error = S::Error;
+
+    fn try_step(&mut self) -> Result<(), S::Error> {
+        BlinqConsumer::try_step(self).map(drop)
+    }
+
+    fn idle(&self) -> bool {
+        BlinqConsumer::idle(self)
+    }
+
+    fn playing(&self) -> bool {
+        self.core.playing()
+    }
+
+    fn try_advance(&mut self, n: usize) -> Result<(), S::Error> {
+        BlinqConsumer::try_advance(self, n).map(drop)
+    }
+
+    fn try_hold(&mut self) -> Result<(), S::Error> {
+        self.core.try_hold()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{FakeGpio, Gpio};
+    use crate::{Blinq, Pattern};
+
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn split() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut blinq: Blinq<Gpio<FakeGpio>, 3> = Blinq::new(fg, false);
+        blinq.enqueue(Pattern::from_u32(0b10, 2));
+
+        {
+            let (mut producer, mut consumer) = blinq.split();
+            assert_eq!(consumer.len(), 1);
+
+            std::thread::scope(|s| {
+                s.spawn(move || {
+                    producer.enqueue(Pattern::from_u32(0b11, 2));
+                    producer.enqueue(Pattern::from_u32(0b11, 2));
+                });
+            });
+
+            for exp in [true, false, true] {
+                consumer.step();
+                assert_eq!(STATE.load(Ordering::SeqCst), exp);
+            }
+            assert!(!consumer.idle());
+        }
+
+        // The split borrows end, and the Blinq carries on where it was
+        assert_eq!(blinq.dropped(), 1);
+        blinq.step();
+        assert!(STATE.load(Ordering::SeqCst));
+        assert!(blinq.idle());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn run() {
+        use embassy_futures::{block_on, select::select, yield_now};
+
+        static STATE: AtomicBool = AtomicBool::new(false);
+
+        // Records the GPIO state each time the driver waits
+        struct Recorder(std::vec::Vec<bool>);
+
+        impl embedded_hal_async::delay::DelayNs for Recorder {
+            async fn delay_ns(&mut self, _ns: u32) {
+                self.0.push(STATE.load(Ordering::SeqCst));
+            }
+        }
+
+        let fg = FakeGpio { state: &STATE };
+        let mut blinq: Blinq<Gpio<FakeGpio>, 3> = Blinq::new(fg, false);
+        let (mut producer, mut consumer) = blinq.split();
+        let mut delay = Recorder(std::vec::Vec::new());
+
+        block_on(select(consumer.run(&mut delay, 1000), async {
+            for _ in 0..10 {
+                yield_now().await;
+            }
+            producer.enqueue(Pattern::from_u32(0b110, 3));
+            for _ in 0..10 {
+                yield_now().await;
+            }
+        }));
+
+        assert_eq!(delay.0, [true, true, false]);
+        assert!(!STATE.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,346 @@
+//! Showing the state of a device
+//!
+//! A [`StatusIndicator`] wraps a [`Blinq`], and loops a pattern for the
+//! state of the application, like booting, connected, or low battery.
+//! Several states can be active at once, and the one with the highest
+//! [priority](Status::priority) is shown.
+//!
+//! The indicator queues its patterns with the tag [`STATUS_TAG`], and only
+//! ever removes or skips patterns with that tag. Other patterns in the
+//! Blinq are played as usual.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use blinq::{Blinq, Pattern, status::{Status, StatusIndicator}};
+//!
+//! #[derive(Clone, Copy, Debug, PartialEq)]
+//! enum Device {
+//!     Connecting,
+//!     Connected,
+//!     LowBattery,
+//! }
+//!
+//! impl Status for Device {
+//!     fn pattern(&self) -> Pattern {
+//!         match self {
+//!             Device::Connecting => Pattern::from_u32(0b10, 2),
+//!             Device::Connected => Pattern::from_u32(0b1000_0000, 8),
+//!             Device::LowBattery => Pattern::from_u32(0b1110, 4),
+//!         }
+//!     }
+//!
+//!     fn priority(&self) -> u8 {
+//!         match self {
+//!             Device::LowBattery => 1,
+//!             _ => 0,
+//!         }
+//!     }
+//! }
+//!
+//! let blinq: Blinq<_, 2> = Blinq::with_sink(|_on: bool| {});
+//! let mut status: StatusIndicator<Device, _, 2, 3> = StatusIndicator::new(blinq);
+//!
+//! status.activate(Device::Connecting).unwrap();
+//! status.step();
+//!
+//! // Low battery takes over once the current blink has finished
+//! status.activate(Device::LowBattery).unwrap();
+//! status.deactivate(Device::Connecting);
+//! status.activate(Device::Connected).unwrap();
+//! assert_eq!(status.shown(), Some(Device::LowBattery));
+//! ```
+
+use heapless::Vec;
+
+use crate::{Blinq, Pattern, Repeat, Sequence, Sink, Step, Tag};
+
+/// The tag of the patterns queued by a [`StatusIndicator`]
+///
+/// This should not be used for other patterns in the same Blinq.
+pub const STATUS_TAG: Tag = Tag::MAX;
+
+/// A state of the application, shown by a [`StatusIndicator`]
+pub trait Status<P = Pattern>: Copy + PartialEq {
+    /// The pattern looped while this state is shown
+    fn pattern(&self) -> P;
+
+    /// How important this state is
+    ///
+    /// While several states are active, the one with the highest priority
+    /// is shown. If there is a tie, the state activated first is shown.
+    fn priority(&self) -> u8;
+}
+
+/// Loops the pattern of the most important active [`Status`]
+///
+/// Up to `K` states of type `T` can be active at once. The indicator only
+/// ever queues one pattern of its own, so an `N` of 2 is enough if nothing
+/// else is queued on the wrapped Blinq.
+///
+/// When the shown state changes, the current cycle of the old pattern is
+/// finished before the new pattern starts, unless [`StatusIndicator::switch_now`]
+/// is called.
+pub struct StatusIndicator<T, S, const N: usize, const K: usize, P = Pattern>
+where
+    S: Sink<P::Value>,
+    P: Sequence,
+{
+    blinq: Blinq<S, N, P>,
+    active: Vec<T, K>,
+    shown: Option<T>,
+}
+
+impl<T, S, const N: usize, const K: usize, P> StatusIndicator<T, S, N, K, P>
+where
+    T: Status<P>,
+    S: Sink<P::Value>,
+    P: Sequence,
+{
+    /// Wrap a Blinq, with no active states
+    ///
+    /// Anything already in the Blinq is played before the first state is
+    /// shown.
+    pub fn new(blinq: Blinq<S, N, P>) -> Self {
+        Self {
+            blinq,
+            active: Vec::new(),
+            shown: None,
+        }
+    }
+
+    /// Release the wrapped Blinq
+    pub fn release(self) -> Blinq<S, N, P> {
+        self.blinq
+    }
+
+    /// Get a reference to the wrapped Blinq
+    pub fn blinq(&self) -> &Blinq<S, N, P> {
+        &self.blinq
+    }
+
+    /// Make a state active
+    ///
+    /// If it is the most important active state, it will be shown once the
+    /// current cycle has finished. Activating a state that is already active
+    /// does nothing. If `K` states are already active, the state is
+    /// returned as an error.
+    pub fn activate(&mut self, state: T) -> Result<(), T> {
+        if !self.is_active(state) {
+            self.active.push(state)?;
+            self.update();
+        }
+        Ok(())
+    }
+
+    /// Make a state inactive
+    ///
+    /// If it was shown, the next most important active state will be shown
+    /// once the current cycle has finished. If no states are left, the
+    /// output is turned off instead.
+    pub fn deactivate(&mut self, state: T) {
+        self.active.retain(|s| *s != state);
+        self.update();
+    }
+
+    /// Is the given state active?
+    pub fn is_active(&self, state: T) -> bool {
+        self.active.contains(&state)
+    }
+
+    /// The state being shown, or about to be shown once the current cycle
+    /// has finished
+    pub fn shown(&self) -> Option<T> {
+        self.shown
+    }
+
+    /// Show the current state from the next step, without waiting for the
+    /// current cycle of the old pattern to finish
+    ///
+    /// Patterns that were not queued by the indicator are never skipped.
+    pub fn switch_now(&mut self) {
+        let own = self.blinq.playing_tag() == Some(STATUS_TAG);
+        if own && (!self.blinq.is_empty() || self.shown.is_none()) {
+            self.blinq.skip();
+        }
+    }
+
+    /// Move the indicator one step
+    ///
+    /// See [`Blinq::step`] for details.
+    pub fn step(&mut self) {
+        let _ = self.try_step();
+    }
+
+    /// Try to move the indicator one step
+    ///
+    /// See [`Blinq::try_step`] for details.
+    pub fn try_step(&mut self) -> Result<(), S::Error> {
+        self.blinq.try_step().map(drop)
+    }
+
+    /// Is nothing being shown?
+    pub fn idle(&self) -> bool {
+        self.blinq.idle()
+    }
+
+    /// Queue the pattern of the most important active state, if it changed
+    fn update(&mut self) {
+        let top = self
+            .active
+            .iter()
+            .fold(None, |top: Option<T>, s| match top {
+                Some(t) if t.priority() >= s.priority() => Some(t),
+                _ => Some(*s),
+            });
+        if top == self.shown {
+            return;
+        }
+        self.shown = top;
+
+        // Only the patterns of the indicator are replaced, anything else
+        // in the Blinq is left to play
+        self.blinq.remove_tagged(STATUS_TAG);
+        self.blinq.cancel_tagged_loop(STATUS_TAG);
+        if let Some(state) = top {
+            self.blinq
+                .enqueue_tagged(state.pattern(), Repeat::Forever, STATUS_TAG);
+        }
+
+        // A loop that has just finished a cycle would otherwise play a
+        // whole cycle more before giving way
+        let own = self.blinq.playing_tag() == Some(STATUS_TAG);
+        if own && self.blinq.between_cycles() {
+            self.blinq.skip();
+        }
+    }
+}
+
+impl<T, S, const N: usize, const K: usize, P> Step for StatusIndicator<T, S, N, K, P>
+where
+    T: Status<P>,
+    S: Sink<P::Value>,
+    P: Sequence,
+{
+    type Error = S::Error;
+
+    fn try_step(&mut self) -> Result<(), S::Error> {
+        StatusIndicator::try_step(self)
+    }
+
+    fn idle(&self) -> bool {
+        StatusIndicator::idle(self)
+    }
+
+    fn playing(&self) -> bool {
+        Step::playing(&self.blinq)
+    }
+
+    fn try_hold(&mut self) -> Result<(), S::Error> {
+        self.blinq.try_hold()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{FakeGpio, Gpio};
+
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Device {
+        Booting,
+        Connected,
+        LowBattery,
+        Fault,
+    }
+
+    impl Status for Device {
+        fn pattern(&self) -> Pattern {
+            match self {
+                Device::Booting => Pattern::from_u32(0b10, 2),
+                Device::Connected => Pattern::from_u32(0b1000, 4),
+                Device::LowBattery => Pattern::from_u32(0b110, 3),
+                Device::Fault => Pattern::from_u32(0b1, 1),
+            }
+        }
+
+        fn priority(&self) -> u8 {
+            match self {
+                Device::Booting | Device::Connected => 1,
+                Device::LowBattery => 2,
+                Device::Fault => 3,
+            }
+        }
+    }
+
+    #[test]
+    fn status() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let blinq: Blinq<Gpio<FakeGpio>, 2> = Blinq::new(FakeGpio { state: &STATE }, false);
+        let mut status: StatusIndicator<Device, _, 2, 3> = StatusIndicator::new(blinq);
+        let check = |status: &mut StatusIndicator<Device, _, 2, 3>, exp: &[u8]| {
+            for (i, exp) in exp.iter().enumerate() {
+                status.step();
+                assert_eq!(STATE.load(Ordering::SeqCst), *exp == 1, "step {}", i);
+            }
+        };
+
+        status.activate(Device::Booting).unwrap();
+        check(&mut status, &[1, 0, 1]);
+
+        // The current cycle is finished first
+        status.deactivate(Device::Booting);
+        assert_eq!(status.shown(), None);
+        status.activate(Device::Connected).unwrap();
+        check(&mut status, &[0, 1, 0, 0, 0, 1]);
+
+        // Unless switching now
+        status.activate(Device::LowBattery).unwrap();
+        status.switch_now();
+        check(&mut status, &[1, 1, 0, 1]);
+
+        // Higher priorities take over, lower ones don't
+        status.activate(Device::Fault).unwrap();
+        assert_eq!(status.activate(Device::Booting), Err(Device::Booting));
+        check(&mut status, &[1, 0, 1, 1]);
+        assert!(status.is_active(Device::Connected));
+
+        // Between cycles, the switch is made straight away
+        status.deactivate(Device::Fault);
+        assert_eq!(status.shown(), Some(Device::LowBattery));
+        check(&mut status, &[1, 1, 0]);
+
+        status.deactivate(Device::Connected);
+        assert_eq!(status.shown(), Some(Device::LowBattery));
+        status.deactivate(Device::LowBattery);
+        check(&mut status, &[0]);
+        assert!(status.idle());
+    }
+
+    #[test]
+    fn shared() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let mut blinq: Blinq<Gpio<FakeGpio>, 4> = Blinq::new(FakeGpio { state: &STATE }, false);
+        let check = |status: &mut StatusIndicator<Device, _, 4, 3>, exp: &[u8]| {
+            for (i, exp) in exp.iter().enumerate() {
+                status.step();
+                assert_eq!(STATE.load(Ordering::SeqCst), *exp == 1, "step {}", i);
+            }
+        };
+
+        // Patterns queued before the first state are played first
+        blinq.enqueue(Pattern::from_u32(0b11, 2));
+        blinq.enqueue_repeat(Pattern::from_u32(0b01, 2), 2);
+        let mut status: StatusIndicator<Device, _, 4, 3> = StatusIndicator::new(blinq);
+        status.activate(Device::Booting).unwrap();
+        check(&mut status, &[1, 1, 0, 1]);
+
+        // A repeated pattern is not cut short between its cycles
+        status.activate(Device::Fault).unwrap();
+        status.switch_now();
+        check(&mut status, &[0, 1, 1, 1]);
+        assert_eq!(status.shown(), Some(Device::Fault));
+    }
+}
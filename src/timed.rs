@@ -0,0 +1,148 @@
+//! Stepping a Blinq based on elapsed time
+//!
+//! This requires the `fugit` feature.
+//!
+//! ## Example
+//!
+//! ```rust
+//! # let led = core::cell::Cell::new(false);
+//! #
+//! use blinq::{timed::Timed, Blinq, patterns};
+//! use fugit::{ExtU64, TimerInstantU64};
+//!
+//! let blinq: Blinq<_, 4> = Blinq::with_sink(|on: bool| led.set(on));
+//!
+//! // Step every 250ms, with a 1kHz monotonic timer
+//! let mut timed: Timed<_, 1_000> = Timed::new(blinq, 250.millis());
+//! timed.inner_mut().enqueue(patterns::morse::SOS);
+//!
+//! // Call this as often as you like, with the current time
+//! let now = TimerInstantU64::<1_000>::from_ticks(1234);
+//! timed.poll(now);
+//! ```
+
+use core::convert::TryFrom;
+
+use fugit::{TimerDurationU64, TimerInstantU64};
+
+use crate::Step;
+
+/// A wrapper that steps a [`Blinq`](crate::Blinq) at a fixed period
+///
+/// Instead of calling `step` at the right rate, call [`Timed::poll`] with
+/// the current time from a monotonic timer running at `HZ`. However many
+/// steps are due since the last poll will be taken, only updating the
+/// output once. Steps are scheduled relative to the first poll, so a late
+/// or jittery poll does not cause the pattern to drift.
+pub struct Timed<B, const HZ: u32> {
+    blinq: B,
+    period: TimerDurationU64<HZ>,
+    next: Option<TimerInstantU64<HZ>>,
+}
+
+impl<B, const HZ: u32> Timed<B, HZ>
+where
+    B: Step,
+{
+    /// Create a new timed wrapper, that steps once every `period`
+    pub fn new(blinq: B, period: TimerDurationU64<HZ>) -> Self {
+        Self {
+            blinq,
+            period,
+            next: None,
+        }
+    }
+
+    /// Take all steps that are due at `now`
+    ///
+    /// The first poll takes a step immediately. If any output errors occur,
+    /// they will be discarded.
+    pub fn poll(&mut self, now: TimerInstantU64<HZ>) {
+        let _ = self.try_poll(now);
+    }
+
+    /// Try to take all steps that are due at `now`
+    ///
+    /// The first poll takes a step immediately. If more than one step is due,
+    /// they are taken at once with [`Step::try_advance`]. If any output errors
+    /// occur, they will be returned, but all due steps are still taken.
+    pub fn try_poll(&mut self, now: TimerInstantU64<HZ>) -> Result<(), B::Error> {
+        let next = *self.next.get_or_insert(now);
+        if now < next {
+            return Ok(());
+        }
+
+        let period = self.period.ticks().max(1);
+        let due = (now - next).ticks() / period + 1;
+        self.next = Some(next + TimerDurationU64::from_ticks(due * period));
+
+        self.blinq
+            .try_advance(usize::try_from(due).unwrap_or(usize::MAX))
+    }
+
+    /// The time of the next step, if any steps have been taken
+    pub fn deadline(&self) -> Option<TimerInstantU64<HZ>> {
+        self.next
+    }
+
+    /// Restart timing, so that the next poll takes a step immediately
+    ///
+    /// This is useful after the Blinq has been idle for a while.
+    pub fn reset(&mut self) {
+        self.next = None;
+    }
+
+    /// Get a reference to the wrapped Blinq
+    pub fn inner(&self) -> &B {
+        &self.blinq
+    }
+
+    /// Get a mutable reference to the wrapped Blinq, for enqueueing patterns
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.blinq
+    }
+
+    /// Consume the wrapper, returning the Blinq
+    pub fn release(self) -> B {
+        self.blinq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{FakeGpio, Gpio};
+    use crate::{Blinq, Pattern};
+
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use fugit::ExtU64;
+
+    #[test]
+    fn catch_up() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut blinq: Blinq<Gpio<FakeGpio>, 3> = Blinq::new(fg, false);
+        blinq.enqueue(Pattern::from_u32(0b1101, 4));
+
+        let mut timed: Timed<_, 1_000> = Timed::new(blinq, 10.millis());
+        let at = TimerInstantU64::<1_000>::from_ticks;
+
+        // First step is immediate
+        timed.poll(at(1000));
+        assert!(STATE.load(Ordering::SeqCst));
+        assert_eq!(timed.deadline(), Some(at(1010)));
+
+        // Not due yet
+        timed.poll(at(1009));
+        assert_eq!(timed.deadline(), Some(at(1010)));
+
+        // Late by one and a half steps, two steps are taken
+        timed.poll(at(1025));
+        assert!(!STATE.load(Ordering::SeqCst));
+        assert_eq!(timed.deadline(), Some(at(1030)));
+
+        timed.poll(at(1030));
+        assert!(STATE.load(Ordering::SeqCst));
+        assert!(timed.inner().idle());
+    }
+}
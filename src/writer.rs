@@ -0,0 +1,175 @@
+//! Writing formatted text to a Blinq as morse code
+//!
+//! ## Example
+//!
+//! ```rust
+//! # let led = core::cell::Cell::new(false);
+//! #
+//! use blinq::{Blinq, writer::MorseWriter};
+//! use core::fmt::Write;
+//!
+//! let mut blinq: Blinq<_, 16> = Blinq::with_sink(|on: bool| led.set(on));
+//!
+//! let code = 42;
+//! write!(MorseWriter::new(&mut blinq), "ERR {}", code).unwrap();
+//! ```
+
+use core::fmt;
+
+use crate::{patterns::morse, Blinq, Pattern, Sequence, Sink};
+
+/// What a [`MorseWriter`] does when the queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnFull {
+    /// Return an error if the text does not fit in the queue
+    ///
+    /// Nothing is enqueued unless the whole string fits, so the write can be
+    /// retried later. Note that `write!` may write its arguments as several
+    /// strings. This is the default.
+    #[default]
+    Error,
+
+    /// Discard characters that don't fit, without returning an error
+    Discard,
+
+    /// Enqueue every character with [`Blinq::enqueue`], applying the
+    /// [overflow policy](Blinq::set_overflow) of the Blinq
+    Overflow,
+}
+
+/// A `core::fmt::Write` adapter that enqueues text as morse code
+///
+/// Each character is enqueued as a separate pattern, with the gaps
+/// between letters and words included. See [`morse::encode`] for details.
+///
+/// Characters without a morse code representation will cause the write
+/// to fail with an error, without enqueueing any of the string.
+pub struct MorseWriter<'a, S, const N: usize, P = Pattern>
+where
+    S: Sink<P::Value>,
+    P: Sequence + From<Pattern>,
+{
+    blinq: &'a mut Blinq<S, N, P>,
+    on_full: OnFull,
+    in_space: bool,
+}
+
+impl<'a, S, const N: usize, P> MorseWriter<'a, S, N, P>
+where
+    S: Sink<P::Value>,
+    P: Sequence + From<Pattern>,
+{
+    /// Create a new writer for the given Blinq
+    ///
+    /// This uses the [`OnFull::Error`] policy.
+    pub fn new(blinq: &'a mut Blinq<S, N, P>) -> Self {
+        Self::with_policy(blinq, OnFull::default())
+    }
+
+    /// Create a new writer for the given Blinq, with the given policy
+    /// for when the queue is full
+    pub fn with_policy(blinq: &'a mut Blinq<S, N, P>, on_full: OnFull) -> Self {
+        Self {
+            blinq,
+            on_full,
+            in_space: false,
+        }
+    }
+}
+
+impl<'a, S, const N: usize, P> fmt::Write for MorseWriter<'a, S, N, P>
+where
+    S: Sink<P::Value>,
+    P: Sequence + From<Pattern>,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Check the whole string first, so that nothing is enqueued on failure
+        let mut in_space = self.in_space;
+        let mut needed = 0;
+        for c in s.chars() {
+            match morse::encode_char(c, &mut in_space) {
+                Some(Ok(_)) => needed += 1,
+                Some(Err(_)) => return Err(fmt::Error),
+                None => {}
+            }
+        }
+        if self.on_full == OnFull::Error && needed > self.blinq.remaining() {
+            return Err(fmt::Error);
+        }
+
+        for c in s.chars() {
+            if let Some(Ok(pat)) = morse::encode_char(c, &mut self.in_space) {
+                match self.on_full {
+                    OnFull::Overflow => self.blinq.enqueue(pat),
+                    _ => {
+                        self.blinq.try_enqueue(pat).ok();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{FakeGpio, Gpio};
+    use crate::Overflow;
+
+    use core::fmt::Write;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn write() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut blinq: Blinq<Gpio<FakeGpio>, 4> = Blinq::new(fg, false);
+
+        // E, word gap, E
+        let c = 'e';
+        write!(MorseWriter::new(&mut blinq), "{}  E", c).unwrap();
+        assert!(MorseWriter::new(&mut blinq).write_str("E").is_err());
+        assert!(MorseWriter::with_policy(&mut blinq, OnFull::Discard)
+            .write_str("E")
+            .is_ok());
+
+        let expected = [true, false, false, false, false, false, false, false];
+        for state in expected.iter().chain(expected[..4].iter()) {
+            blinq.step();
+            assert_eq!(STATE.load(Ordering::SeqCst), *state);
+        }
+        assert!(blinq.idle());
+    }
+
+    #[test]
+    fn full() {
+        let mut blinq: Blinq<_, 4> = Blinq::with_sink(|_on: bool| {});
+        blinq.enqueue(Pattern::from_u32(0b1, 1));
+
+        // Nothing is enqueued if it doesn't all fit
+        assert!(MorseWriter::new(&mut blinq).write_str("EEE").is_err());
+        assert_eq!(blinq.len(), 1);
+        assert!(MorseWriter::new(&mut blinq).write_str("EE").is_ok());
+        assert_eq!(blinq.len(), 3);
+
+        // The overflow policy of the Blinq is used
+        blinq.set_overflow(Overflow::DropOldest);
+        MorseWriter::with_policy(&mut blinq, OnFull::Overflow)
+            .write_str("T")
+            .unwrap();
+        assert_eq!(blinq.dropped(), 1);
+        assert_eq!(blinq.peek(), Some(&morse::E.append(&morse::LETTER_GAP)));
+    }
+
+    #[test]
+    fn unsupported() {
+        static STATE: AtomicBool = AtomicBool::new(false);
+        let fg = FakeGpio { state: &STATE };
+        let mut blinq: Blinq<Gpio<FakeGpio>, 4> = Blinq::new(fg, false);
+
+        assert!(write!(MorseWriter::new(&mut blinq), "{{}}").is_err());
+        assert!(MorseWriter::new(&mut blinq).write_str("E%").is_err());
+        assert!(blinq.idle());
+    }
+}